@@ -0,0 +1,130 @@
+use async_io::Timer;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{affinity, metadata, perf, utilization, Joiner, ParallelLH, StackSize};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pin_utils::pin_mut;
+use std::future::Future;
+use std::time::Duration;
+
+const STACK_SIZE_MB: usize = 16;
+const FAN_OUT_LATENCY_MS: u64 = 1;
+const FAN_OUT_WIDTH: [usize; 5] = [1, 10, 100, 1_000, 10_000];
+
+fn param_string(width: usize, cores: usize) -> String {
+    format!(
+        "Width: {} | Latency ms: {} | Cores: {}",
+        width, FAN_OUT_LATENCY_MS, cores
+    )
+}
+
+/// The latency hiding path of `inject_latency`, for an arbitrary future.
+fn await_future_job<F: Future + Send>(future: F) {
+    let future_job = rayon::FutureJob::new(future);
+    pin_mut!(future_job);
+    future_job.spawn().await_future_job();
+}
+
+/// Binary join tree with `width` leaves, each awaiting a timer as a `FutureJob`, so up to `width`
+/// jobs are outstanding at the same time.
+fn fan_out(width: usize, latency: Duration) {
+    if width <= 1 {
+        await_future_job(Timer::after(latency));
+        return;
+    }
+
+    ParallelLH::join(
+        || fan_out(width / 2, latency),
+        || fan_out(width - width / 2, latency),
+    );
+}
+
+fn future_job_overhead_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("FutureJob Overhead");
+    metadata::write_criterion_metadata("FutureJob Overhead");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    // Setting up and tearing down threadpool in inner loop, but whatever
+    for cores in num_cores {
+        let pool = ThreadPoolConfig::new()
+            .threads(cores)
+            .placement(affinity::placement_from_env())
+            .stack_size(StackSize::Mb(STACK_SIZE_MB))
+            .build()
+            .unwrap();
+
+        bench_group.throughput(Throughput::Elements(1));
+
+        bench_group.bench_function(
+            BenchmarkId::new("Ready Future", format!("Cores: {}", cores)),
+            |b| {
+                utilization::measure(|| {
+                    pool.install(|| {
+                        b.iter(|| perf::measure(|| await_future_job(async { black_box(0) })))
+                    })
+                })
+            },
+        );
+        perf::report(format!("Ready Future/Cores: {}", cores));
+        utilization::save(
+            "FutureJob Overhead",
+            "Ready Future",
+            &format!("Cores: {}", cores),
+        );
+
+        bench_group.bench_function(
+            BenchmarkId::new("Zero Timer", format!("Cores: {}", cores)),
+            |b| {
+                utilization::measure(|| {
+                    pool.install(|| {
+                        b.iter(|| perf::measure(|| await_future_job(Timer::after(Duration::ZERO))))
+                    })
+                })
+            },
+        );
+        perf::report(format!("Zero Timer/Cores: {}", cores));
+        utilization::save(
+            "FutureJob Overhead",
+            "Zero Timer",
+            &format!("Cores: {}", cores),
+        );
+
+        for width in FAN_OUT_WIDTH {
+            bench_group.throughput(Throughput::Elements(width as u64));
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Fan Out", param_string(width, cores)),
+                &width,
+                |b, &w| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    fan_out(black_box(w), Duration::from_millis(FAN_OUT_LATENCY_MS))
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Fan Out/{}", param_string(width, cores)));
+            utilization::save("FutureJob Overhead", "Fan Out", &param_string(width, cores));
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = future_job_overhead_bench
+}
+criterion_main!(benches);
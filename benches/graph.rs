@@ -0,0 +1,164 @@
+use benchmarks::graph::{bfs, dfs, Graph, TraversalStats};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, Serial, StackSize, Work,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const STACK_SIZE_MB: usize = 32; // set a large stack size to avoid overflow, dfs recurses deeply
+const LATENCY_MS: [Option<u64>; 3] = [None, Some(1), Some(10)];
+const SEED: u64 = 0;
+const SOURCE: u32 = 0;
+const VERTICES: usize = 4096;
+const AVERAGE_DEGREE: f64 = 8.0;
+const RMAT_SCALE: u32 = 12; // same number of vertices as the Erdős–Rényi graph
+const RMAT_EDGE_FACTOR: usize = 4; // same number of edges as the Erdős–Rényi graph
+
+fn param_string(graph: &str, traversal: &str, latency_ms: Option<u64>, cores: usize) -> String {
+    format!(
+        "Graph: {} | Traversal: {} | Latency ms: {} | Cores: {}",
+        graph,
+        traversal,
+        latency_ms.unwrap_or(0),
+        cores
+    )
+}
+
+fn traverse<J: Joiner>(traversal: &str, graph: &Graph, work: &Work) -> TraversalStats {
+    match traversal {
+        "BFS" => bfs::<J>(graph, SOURCE, work),
+        "DFS" => dfs::<J>(graph, SOURCE, work),
+        _ => unreachable!(),
+    }
+}
+
+fn graph_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Graph Traversal");
+    metadata::write_criterion_metadata("Graph Traversal");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let graphs = [
+        (
+            "Erdos-Renyi",
+            Graph::erdos_renyi(VERTICES, AVERAGE_DEGREE, SEED),
+        ),
+        ("R-MAT", Graph::rmat(RMAT_SCALE, RMAT_EDGE_FACTOR, SEED)),
+    ];
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for (name, graph) in graphs.iter() {
+        for traversal in ["BFS", "DFS"] {
+            for latency_ms in LATENCY_MS {
+                let work = Work::new(latency_ms, None);
+
+                // Serial benchmark
+                bench_group.bench_with_input(
+                    BenchmarkId::new("Serial", param_string(name, traversal, latency_ms, 1)),
+                    &work,
+                    |b, w| {
+                        b.iter(|| {
+                            perf::measure(|| {
+                                traverse::<Serial>(traversal, black_box(graph), black_box(w))
+                            })
+                        })
+                    },
+                );
+                perf::report(format!(
+                    "Serial/{}",
+                    param_string(name, traversal, latency_ms, 1)
+                ));
+
+                // Parallel benchmarks
+                // Setting up and tearing down threadpool in inner loop, but whatever
+                for cores in num_cores.clone() {
+                    let pool = ThreadPoolConfig::new()
+                        .threads(cores)
+                        .placement(affinity::placement_from_env())
+                        .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                        .build()
+                        .unwrap();
+
+                    bench_group.bench_with_input(
+                        BenchmarkId::new(
+                            "Classic",
+                            param_string(name, traversal, latency_ms, cores),
+                        ),
+                        &work,
+                        |b, w| {
+                            utilization::measure(|| {
+                                pool.install(|| {
+                                    b.iter(|| {
+                                        perf::measure(|| {
+                                            traverse::<Parallel>(
+                                                traversal,
+                                                black_box(graph),
+                                                black_box(w),
+                                            )
+                                        })
+                                    })
+                                })
+                            })
+                        },
+                    );
+                    perf::report(format!(
+                        "Classic/{}",
+                        param_string(name, traversal, latency_ms, cores)
+                    ));
+                    utilization::save(
+                        "Graph Traversal",
+                        "Classic",
+                        &param_string(name, traversal, latency_ms, cores),
+                    );
+
+                    bench_group.bench_with_input(
+                        BenchmarkId::new(
+                            "Latency Hiding",
+                            param_string(name, traversal, latency_ms, cores),
+                        ),
+                        &work,
+                        |b, w| {
+                            utilization::measure(|| {
+                                pool.install(|| {
+                                    b.iter(|| {
+                                        perf::measure(|| {
+                                            traverse::<ParallelLH>(
+                                                traversal,
+                                                black_box(graph),
+                                                black_box(w),
+                                            )
+                                        })
+                                    })
+                                })
+                            })
+                        },
+                    );
+                    perf::report(format!(
+                        "Latency Hiding/{}",
+                        param_string(name, traversal, latency_ms, cores)
+                    ));
+                    utilization::save(
+                        "Graph Traversal",
+                        "Latency Hiding",
+                        &param_string(name, traversal, latency_ms, cores),
+                    );
+                }
+            }
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = graph_bench
+}
+criterion_main!(benches);
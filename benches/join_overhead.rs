@@ -0,0 +1,125 @@
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, ParallelOldRayon, Serial,
+    StackSize,
+};
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
+};
+
+const STACK_SIZE_MB: usize = 16;
+const DEPTH: [u32; 3] = [10, 15, 20];
+const LEAF_ITERATIONS: [u64; 2] = [0, 100]; // empty and near-empty leaves
+
+fn param_string(depth: u32, leaf_iterations: u64, cores: usize) -> String {
+    format!(
+        "Depth: {} | Leaf iterations: {} | Cores: {}",
+        depth, leaf_iterations, cores
+    )
+}
+
+/// Complete binary join tree of the given depth, i.e. `2^depth - 1` joins.
+fn join_tree<J: Joiner>(depth: u32, leaf_iterations: u64) -> u64 {
+    if depth == 0 {
+        return (0..leaf_iterations).fold(0, |acc, i| black_box(acc + i));
+    }
+
+    let (a, b) = J::join(
+        || join_tree::<J>(depth - 1, leaf_iterations),
+        || join_tree::<J>(depth - 1, leaf_iterations),
+    );
+
+    a.wrapping_add(b)
+}
+
+fn bench_joiner<J: Joiner>(
+    bench_group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    cores: usize,
+    install: impl Fn(&mut (dyn FnMut() + Send)),
+) {
+    for depth in DEPTH {
+        bench_group.throughput(Throughput::Elements((1 << depth) - 1));
+
+        for leaf_iterations in LEAF_ITERATIONS {
+            bench_group.bench_with_input(
+                BenchmarkId::new(name, param_string(depth, leaf_iterations, cores)),
+                &(depth, leaf_iterations),
+                |b, &(d, l)| {
+                    install(&mut || {
+                        b.iter(|| perf::measure(|| join_tree::<J>(black_box(d), black_box(l))))
+                    })
+                },
+            );
+            perf::report(format!(
+                "{}/{}",
+                name,
+                param_string(depth, leaf_iterations, cores)
+            ));
+            // Serial runs on the bench thread, without a pool to measure
+            if name != "Serial" {
+                utilization::save(
+                    "Join Overhead",
+                    name,
+                    &param_string(depth, leaf_iterations, cores),
+                );
+            }
+        }
+    }
+}
+
+fn join_overhead_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Join Overhead");
+    metadata::write_criterion_metadata("Join Overhead");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    bench_joiner::<Serial>(&mut bench_group, "Serial", 1, |f| f());
+
+    // Setting up and tearing down threadpool in inner loop, but whatever
+    for cores in num_cores {
+        let old_pool = ThreadPoolConfig::new()
+            .threads(cores)
+            .placement(affinity::placement_from_env())
+            .stack_size(StackSize::Mb(STACK_SIZE_MB))
+            .build_old()
+            .unwrap();
+
+        bench_joiner::<ParallelOldRayon>(&mut bench_group, "Old Rayon", cores, |f| {
+            utilization::measure(|| old_pool.install(f))
+        });
+
+        drop(old_pool);
+
+        let pool = ThreadPoolConfig::new()
+            .threads(cores)
+            .placement(affinity::placement_from_env())
+            .stack_size(StackSize::Mb(STACK_SIZE_MB))
+            .build()
+            .unwrap();
+
+        bench_joiner::<Parallel>(&mut bench_group, "Classic", cores, |f| {
+            utilization::measure(|| pool.install(f))
+        });
+        bench_joiner::<ParallelLH>(&mut bench_group, "Latency Hiding", cores, |f| {
+            utilization::measure(|| pool.install(f))
+        });
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = join_overhead_bench
+}
+criterion_main!(benches);
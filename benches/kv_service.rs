@@ -0,0 +1,114 @@
+use benchmarks::fib::fib;
+use benchmarks::kv_service::KvServer;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{affinity, metadata, perf, utilization, Parallel, ParallelLH, StackSize, Work};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const FIB_N: u32 = 12;
+const FIB_SERIAL_CUTOFF: u32 = 0; // needs to be 0 so every leaf sends a request
+
+const STACK_SIZE_MB: usize = 16; // set a large stack size to avoid overflow
+const SERVICE_MS: [u64; 3] = [0, 1, 10];
+const KV_CONCURRENCY: usize = 64;
+
+fn param_string(service_ms: u64, cores: usize) -> String {
+    format!(
+        "Service ms: {} | Concurrency: {} | Cores: {}",
+        service_ms, KV_CONCURRENCY, cores
+    )
+}
+
+fn kv_service_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("KV Service Fib");
+    metadata::write_criterion_metadata("KV Service Fib");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for service_ms in SERVICE_MS {
+        let server = KvServer::start(service_ms, KV_CONCURRENCY).unwrap();
+        let work = Work::RemoteRequest {
+            addr: server.addr(),
+        };
+
+        // Setting up and tearing down threadpool in inner loop, but whatever
+        for cores in num_cores.clone() {
+            let pool = ThreadPoolConfig::new()
+                .threads(cores)
+                .placement(affinity::placement_from_env())
+                .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                .build()
+                .unwrap();
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Classic", param_string(service_ms, cores)),
+                &work,
+                |b, w| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    fib::<Parallel>(
+                                        black_box(FIB_N),
+                                        black_box(w),
+                                        black_box(FIB_SERIAL_CUTOFF),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Classic/{}", param_string(service_ms, cores)));
+            utilization::save(
+                "KV Service Fib",
+                "Classic",
+                &param_string(service_ms, cores),
+            );
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Latency Hiding", param_string(service_ms, cores)),
+                &work,
+                |b, w| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    fib::<ParallelLH>(
+                                        black_box(FIB_N),
+                                        black_box(w),
+                                        black_box(FIB_SERIAL_CUTOFF),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!(
+                "Latency Hiding/{}",
+                param_string(service_ms, cores)
+            ));
+            utilization::save(
+                "KV Service Fib",
+                "Latency Hiding",
+                &param_string(service_ms, cores),
+            );
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = kv_service_bench
+}
+criterion_main!(benches);
@@ -1,6 +1,9 @@
-use benchmarks::map_reduce::map_reduce;
 use benchmarks::map_reduce::map_reduce_fib;
-use benchmarks::{Joiner, Parallel, ParallelLH, Serial, Work};
+use benchmarks::map_reduce::{map_reduce, map_reduce_async, Grain};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, Serial, StackSize, Work,
+};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use std::iter::Iterator;
 
@@ -52,8 +55,28 @@ fn map_reduce_fib<J: Joiner>(
     )
 }
 
+fn map_reduce_async_fib<J: Joiner>(
+    input: &mut [u32],
+    latency_ms: Option<u64>,
+    serial_cutoff: u32,
+) -> u32 {
+    let map = |&mut n: &mut u32| map_reduce_fib::async_map::<J>(n, latency_ms, serial_cutoff);
+
+    map_reduce_async::<J, _, _, _, _, _, _>(
+        input,
+        Grain::Fixed(1),
+        &map,
+        &map_reduce_fib::reduce,
+        &map_reduce_fib::identity,
+    )
+}
+
 fn map_reduce_fib_bench(c: &mut Criterion) {
     let mut bench_group = c.benchmark_group("MapReduce Fib");
+    metadata::write_criterion_metadata("MapReduce Fib");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
 
     let num_cores = {
         let step = if num_cpus::get() <= 10 { 2 } else { 5 };
@@ -66,70 +89,80 @@ fn map_reduce_fib_bench(c: &mut Criterion) {
             let mut input = vec![fib_n; len];
 
             for latency_ms in LATENCY_MS {
-                // Serial benchmark
-                // bench_group.bench_with_input(
-                    // BenchmarkId::new(
-                        // "Serial",
-                        // param_string(len, latency_ms, 1, (fib_n, serial_cutoff)),
-                    // ),
-                    // &latency_ms,
-                    // |b, &l| {
-                        // b.iter(|| {
-                            // map_reduce_fib::<Serial>(
-                                // black_box(&mut input),
-                                // black_box(l),
-                                // black_box(serial_cutoff),
-                            // )
-                        // })
-                    // },
-                // );
+                // Serial benchmark, T1 for speedups and the model
+                let params = param_string(len, latency_ms, 1, (fib_n, serial_cutoff));
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new("Serial", &params),
+                    &latency_ms,
+                    |b, &l| {
+                        b.iter(|| {
+                            perf::measure(|| {
+                                map_reduce_fib::<Serial>(
+                                    black_box(&mut input),
+                                    black_box(l),
+                                    black_box(serial_cutoff),
+                                )
+                            })
+                        })
+                    },
+                );
+                perf::report(format!("Serial/{}", params));
 
                 // Parallel benchmarks
                 // Setting up and tearing down threadpool in inner loop, but whatever
                 for cores in num_cores.clone() {
-                    let pool = rayon::ThreadPoolBuilder::new()
-                        .num_threads(cores)
-                        .stack_size(STACK_SIZE_MB * 1024 * 1024)
+                    let pool = ThreadPoolConfig::new()
+                        .threads(cores)
+                        .placement(affinity::placement_from_env())
+                        .stack_size(StackSize::Mb(STACK_SIZE_MB))
                         .build()
                         .unwrap();
+                    let params = param_string(len, latency_ms, cores, (fib_n, serial_cutoff));
 
                     bench_group.bench_with_input(
-                        BenchmarkId::new(
-                            "Classic",
-                            param_string(len, latency_ms, cores, (fib_n, serial_cutoff)),
-                        ),
+                        BenchmarkId::new("Classic", &params),
                         &latency_ms,
                         |b, &l| {
-                            pool.install(|| {
-                                b.iter(|| {
-                                    map_reduce_fib::<Parallel>(
-                                        black_box(&mut input),
-                                        black_box(l),
-                                        black_box(serial_cutoff),
-                                    )
+                            utilization::measure(|| {
+                                pool.install(|| {
+                                    b.iter(|| {
+                                        perf::measure(|| {
+                                            map_reduce_fib::<Parallel>(
+                                                black_box(&mut input),
+                                                black_box(l),
+                                                black_box(serial_cutoff),
+                                            )
+                                        })
+                                    })
                                 })
                             })
                         },
                     );
+                    perf::report(format!("Classic/{}", params));
+                    utilization::save("MapReduce Fib", "Classic", &params);
 
                     bench_group.bench_with_input(
-                        BenchmarkId::new(
-                            "Latency Hiding",
-                            param_string(len, latency_ms, cores, (fib_n, serial_cutoff)),
-                        ),
+                        BenchmarkId::new("Latency Hiding", &params),
                         &latency_ms,
                         |b, &l| {
-                            pool.install(|| {
-                                b.iter(|| {
-                                    map_reduce_fib::<ParallelLH>(
-                                        black_box(&mut input),
-                                        black_box(l),
-                                        black_box(serial_cutoff),
-                                    )
+                            utilization::measure(|| {
+                                pool.install(|| {
+                                    b.iter(|| {
+                                        perf::measure(|| {
+                                            map_reduce_fib::<ParallelLH>(
+                                                black_box(&mut input),
+                                                black_box(l),
+                                                black_box(serial_cutoff),
+                                            )
+                                        })
+                                    })
                                 })
                             })
                         },
                     );
+                    perf::report(format!("Latency Hiding/{}", params));
+                    utilization::save("MapReduce Fib", "Latency Hiding", &params);
                 }
             }
         }
@@ -138,10 +171,72 @@ fn map_reduce_fib_bench(c: &mut Criterion) {
     bench_group.finish();
 }
 
+fn map_reduce_async_fib_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("MapReduce Async Fib");
+    metadata::write_criterion_metadata("MapReduce Async Fib");
+
+    // Use all cores available
+    let cores = num_cpus::get();
+    let pool = ThreadPoolConfig::new()
+        .threads(cores)
+        .placement(affinity::placement_from_env())
+        .stack_size(StackSize::Mb(STACK_SIZE_MB))
+        .build()
+        .unwrap();
+
+    for len in LEN {
+        for (fib_n, serial_cutoff) in FIB_SETTINGS {
+            let mut input = vec![fib_n; len];
+
+            for latency_ms in LATENCY_MS {
+                bench_group.bench_with_input(
+                    BenchmarkId::new(
+                        "Classic",
+                        param_string(len, latency_ms, cores, (fib_n, serial_cutoff)),
+                    ),
+                    &latency_ms,
+                    |b, &l| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                map_reduce_async_fib::<Parallel>(
+                                    black_box(&mut input),
+                                    black_box(l),
+                                    black_box(serial_cutoff),
+                                )
+                            })
+                        })
+                    },
+                );
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new(
+                        "Latency Hiding",
+                        param_string(len, latency_ms, cores, (fib_n, serial_cutoff)),
+                    ),
+                    &latency_ms,
+                    |b, &l| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                map_reduce_async_fib::<ParallelLH>(
+                                    black_box(&mut input),
+                                    black_box(l),
+                                    black_box(serial_cutoff),
+                                )
+                            })
+                        })
+                    },
+                );
+            }
+        }
+    }
+
+    bench_group.finish();
+}
+
 criterion_group! {
   name = benches;
   // config = Criterion::default().sample_size(35);
   config = Criterion::default().sample_size(10);
-  targets = map_reduce_fib_bench
+  targets = map_reduce_fib_bench, map_reduce_async_fib_bench
 }
 criterion_main!(benches);
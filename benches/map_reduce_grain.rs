@@ -0,0 +1,165 @@
+use benchmarks::map_reduce::{map_reduce_fib, map_reduce_with_grain, Grain};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, StackSize, Work,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// (fib n, serial_cutoff)
+type FibSettings = (u32, u32);
+
+const STACK_SIZE_MB: usize = 24; // set a large stack size to avoid overflow
+const LATENCY_MS: [Option<u64>; 3] = [None, Some(1), Some(50)];
+const LEN: usize = 2000;
+const FIB_SETTINGS: FibSettings = (20, 15);
+const GRAINS: [Grain; 6] = [
+    Grain::Fixed(1),
+    Grain::Fixed(4),
+    Grain::Fixed(16),
+    Grain::Fixed(64),
+    Grain::Fixed(256),
+    Grain::Adaptive,
+];
+
+fn param_string(
+    length: usize,
+    grain: Grain,
+    latency_ms: Option<u64>,
+    cores: usize,
+    fib_settings: FibSettings,
+) -> String {
+    format!(
+        "Length: {} | Grain: {} | Latency ms: {} | Cores: {} | Fib N: {} | Cutoff: {}",
+        length,
+        grain,
+        latency_ms.unwrap_or(0),
+        cores,
+        fib_settings.0,
+        fib_settings.1
+    )
+}
+
+fn map_reduce_fib<J: Joiner>(
+    input: &mut [u32],
+    grain: Grain,
+    latency_ms: Option<u64>,
+    serial_cutoff: u32,
+) -> u32 {
+    fn constrain<F>(f: F) -> F
+    where
+        F: for<'a> Fn(&'a mut u32) -> u32,
+    {
+        f
+    }
+
+    let map = constrain(|&mut n| {
+        map_reduce_fib::map::<J>(n, &Work::new(latency_ms, None), serial_cutoff)
+    });
+
+    map_reduce_with_grain::<J, _, _, _, _, _>(
+        input,
+        grain,
+        &map,
+        &map_reduce_fib::reduce,
+        &map_reduce_fib::identity,
+    )
+}
+
+fn map_reduce_grain_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("MapReduce Grain");
+    metadata::write_criterion_metadata("MapReduce Grain");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let (fib_n, serial_cutoff) = FIB_SETTINGS;
+    let mut input = vec![fib_n; LEN];
+
+    // Use all cores available, we are interested in how grain size interacts with latency
+    let cores = num_cpus::get();
+    let pool = ThreadPoolConfig::new()
+        .threads(cores)
+        .placement(affinity::placement_from_env())
+        .stack_size(StackSize::Mb(STACK_SIZE_MB))
+        .build()
+        .unwrap();
+
+    for latency_ms in LATENCY_MS {
+        for grain in GRAINS {
+            bench_group.bench_with_input(
+                BenchmarkId::new(
+                    "Classic",
+                    param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS),
+                ),
+                &latency_ms,
+                |b, &l| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    map_reduce_fib::<Parallel>(
+                                        black_box(&mut input),
+                                        black_box(grain),
+                                        black_box(l),
+                                        black_box(serial_cutoff),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!(
+                "Classic/{}",
+                param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS)
+            ));
+            utilization::save(
+                "MapReduce Grain",
+                "Classic",
+                &param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS),
+            );
+
+            bench_group.bench_with_input(
+                BenchmarkId::new(
+                    "Latency Hiding",
+                    param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS),
+                ),
+                &latency_ms,
+                |b, &l| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    map_reduce_fib::<ParallelLH>(
+                                        black_box(&mut input),
+                                        black_box(grain),
+                                        black_box(l),
+                                        black_box(serial_cutoff),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!(
+                "Latency Hiding/{}",
+                param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS)
+            ));
+            utilization::save(
+                "MapReduce Grain",
+                "Latency Hiding",
+                &param_string(LEN, grain, latency_ms, cores, FIB_SETTINGS),
+            );
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = map_reduce_grain_bench
+}
+criterion_main!(benches);
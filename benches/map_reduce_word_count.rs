@@ -0,0 +1,155 @@
+use benchmarks::map_reduce::map_reduce;
+use benchmarks::map_reduce::map_reduce_word_count::{self, WordCounts};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, Serial, StackSize, Work,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// (documents, words per document, vocabulary size)
+type CorpusSettings = (usize, usize, usize);
+
+const STACK_SIZE_MB: usize = 24; // set a large stack size to avoid overflow
+const LATENCY_MS: [Option<u64>; 4] = [None, Some(1), Some(50), Some(100)];
+const CORPUS_SETTINGS: [CorpusSettings; 2] = [(2000, 1000, 10_000), (2000, 1000, 200_000)];
+const SEED: u64 = 0;
+
+fn param_string(latency_ms: Option<u64>, cores: usize, corpus_settings: CorpusSettings) -> String {
+    format!(
+        "Documents: {} | Words: {} | Vocabulary: {} | Latency ms: {} | Cores: {}",
+        corpus_settings.0,
+        corpus_settings.1,
+        corpus_settings.2,
+        latency_ms.unwrap_or(0),
+        cores
+    )
+}
+
+fn word_count<J: Joiner>(corpus: &mut [String], latency_ms: Option<u64>) -> WordCounts {
+    let work = Work::new(latency_ms, None);
+    let map = |document: &mut String| map_reduce_word_count::map::<J>(document, &work);
+
+    map_reduce::<J, _, _, _, _, _>(
+        corpus,
+        &map,
+        &map_reduce_word_count::reduce,
+        &map_reduce_word_count::identity,
+    )
+}
+
+fn word_count_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("MapReduce Word Count");
+    metadata::write_criterion_metadata("MapReduce Word Count");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for corpus_settings in CORPUS_SETTINGS {
+        let (documents, words_per_document, vocabulary_size) = corpus_settings;
+        let mut corpus = map_reduce_word_count::generate_corpus(
+            SEED,
+            documents,
+            words_per_document,
+            vocabulary_size,
+        );
+
+        for latency_ms in LATENCY_MS {
+            // Serial benchmark
+            bench_group.bench_with_input(
+                BenchmarkId::new("Serial", param_string(latency_ms, 1, corpus_settings)),
+                &latency_ms,
+                |b, &l| {
+                    b.iter(|| {
+                        perf::measure(|| word_count::<Serial>(black_box(&mut corpus), black_box(l)))
+                    })
+                },
+            );
+            perf::report(format!(
+                "Serial/{}",
+                param_string(latency_ms, 1, corpus_settings)
+            ));
+
+            // Parallel benchmarks
+            // Setting up and tearing down threadpool in inner loop, but whatever
+            for cores in num_cores.clone() {
+                let pool = ThreadPoolConfig::new()
+                    .threads(cores)
+                    .placement(affinity::placement_from_env())
+                    .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                    .build()
+                    .unwrap();
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new("Classic", param_string(latency_ms, cores, corpus_settings)),
+                    &latency_ms,
+                    |b, &l| {
+                        utilization::measure(|| {
+                            pool.install(|| {
+                                b.iter(|| {
+                                    perf::measure(|| {
+                                        word_count::<Parallel>(black_box(&mut corpus), black_box(l))
+                                    })
+                                })
+                            })
+                        })
+                    },
+                );
+                perf::report(format!(
+                    "Classic/{}",
+                    param_string(latency_ms, cores, corpus_settings)
+                ));
+                utilization::save(
+                    "MapReduce Word Count",
+                    "Classic",
+                    &param_string(latency_ms, cores, corpus_settings),
+                );
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new(
+                        "Latency Hiding",
+                        param_string(latency_ms, cores, corpus_settings),
+                    ),
+                    &latency_ms,
+                    |b, &l| {
+                        utilization::measure(|| {
+                            pool.install(|| {
+                                b.iter(|| {
+                                    perf::measure(|| {
+                                        word_count::<ParallelLH>(
+                                            black_box(&mut corpus),
+                                            black_box(l),
+                                        )
+                                    })
+                                })
+                            })
+                        })
+                    },
+                );
+                perf::report(format!(
+                    "Latency Hiding/{}",
+                    param_string(latency_ms, cores, corpus_settings)
+                ));
+                utilization::save(
+                    "MapReduce Word Count",
+                    "Latency Hiding",
+                    &param_string(latency_ms, cores, corpus_settings),
+                );
+            }
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = word_count_bench
+}
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+use benchmarks::map_reduce::map_reduce;
+use benchmarks::migration::{self, memory_heavy_leaf};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelLH, StackSize, Work,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const STACK_SIZE_MB: usize = 16; // set a large stack size to avoid overflow
+const LEN: usize = 1000;
+const LATENCY_MS: u64 = 1;
+const WORKING_SET_KB: [usize; 3] = [16, 256, 4096]; // roughly L1, L2 and L3 sized
+
+fn param_string(working_set_kb: usize, cores: usize) -> String {
+    format!(
+        "Length: {} | Latency ms: {} | Working set KB: {} | Cores: {}",
+        LEN, LATENCY_MS, working_set_kb, cores
+    )
+}
+
+fn memory_heavy_map_reduce<J: Joiner>(work: &Work, working_set_kb: usize) -> u64 {
+    let mut items = vec![(); LEN];
+    let map = |_: &mut ()| memory_heavy_leaf::<J>(work, working_set_kb);
+
+    map_reduce::<J, _, _, _, _, _>(&mut items, &map, &u64::wrapping_add, &|| 0)
+}
+
+fn migration_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Migration");
+    metadata::write_criterion_metadata("Migration");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+    migration::enable();
+    let work = Work::new(Some(LATENCY_MS), None);
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for working_set_kb in WORKING_SET_KB {
+        // Setting up and tearing down threadpool in inner loop, but whatever
+        for cores in num_cores.clone() {
+            let pool = ThreadPoolConfig::new()
+                .threads(cores)
+                .placement(affinity::placement_from_env())
+                .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                .build()
+                .unwrap();
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Classic", param_string(working_set_kb, cores)),
+                &working_set_kb,
+                |b, &kb| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    memory_heavy_map_reduce::<Parallel>(black_box(&work), kb)
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Classic/{}", param_string(working_set_kb, cores)));
+            utilization::save("Migration", "Classic", &param_string(working_set_kb, cores));
+            println!("Classic: {}", migration::stats());
+            migration::reset();
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Latency Hiding", param_string(working_set_kb, cores)),
+                &working_set_kb,
+                |b, &kb| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    memory_heavy_map_reduce::<ParallelLH>(black_box(&work), kb)
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!(
+                "Latency Hiding/{}",
+                param_string(working_set_kb, cores)
+            ));
+            utilization::save(
+                "Migration",
+                "Latency Hiding",
+                &param_string(working_set_kb, cores),
+            );
+            println!("Latency Hiding: {}", migration::stats());
+            migration::reset();
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = migration_bench
+}
+criterion_main!(benches);
@@ -1,6 +1,10 @@
 use benchmarks::map_reduce::map_reduce;
 use benchmarks::map_reduce::map_reduce_fib;
-use benchmarks::{Joiner, Parallel, ParallelOldRayon, Serial, Work};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Joiner, Parallel, ParallelOldRayon, Serial, StackSize,
+    Work,
+};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
 // (fib n, serial_cutoff)
@@ -53,6 +57,10 @@ fn map_reduce_fib<J: Joiner>(
 
 fn map_reduce_fib_bench(c: &mut Criterion) {
     let mut bench_group = c.benchmark_group("Old vs New Rayon");
+    metadata::write_criterion_metadata("Old vs New Rayon");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
 
     let (fib_n, serial_cutoff) = FIB_SETTINGS;
     let mut input = vec![fib_n; LEN];
@@ -72,69 +80,84 @@ fn map_reduce_fib_bench(c: &mut Criterion) {
         &LATENCY_MS,
         |b, &l| {
             b.iter(|| {
-                map_reduce_fib::<Serial>(
-                    black_box(&mut input),
-                    black_box(l),
-                    black_box(serial_cutoff),
-                )
+                perf::measure(|| {
+                    map_reduce_fib::<Serial>(
+                        black_box(&mut input),
+                        black_box(l),
+                        black_box(serial_cutoff),
+                    )
+                })
             })
         },
     );
+    perf::report(format!(
+        "Serial/{}",
+        param_string(LEN, LATENCY_MS, 1, (fib_n, serial_cutoff))
+    ));
 
     for cores in num_cores.clone() {
-        let old_pool = rayon_old::ThreadPoolBuilder::new()
-            .num_threads(cores)
-            .stack_size(STACK_SIZE_MB * 1024 * 1024)
-            .build()
+        let params = param_string(LEN, LATENCY_MS, cores, (fib_n, serial_cutoff));
+        let old_pool = ThreadPoolConfig::new()
+            .threads(cores)
+            .placement(affinity::placement_from_env())
+            .stack_size(StackSize::Mb(STACK_SIZE_MB))
+            .build_old()
             .unwrap();
 
         // Old Rayon benchmark
         bench_group.bench_with_input(
-            BenchmarkId::new(
-                "Old Rayon",
-                param_string(LEN, LATENCY_MS, cores, (fib_n, serial_cutoff)),
-            ),
+            BenchmarkId::new("Old Rayon", &params),
             &LATENCY_MS,
             |b, &l| {
-                old_pool.install(|| {
-                    b.iter(|| {
-                        map_reduce_fib::<ParallelOldRayon>(
-                            black_box(&mut input),
-                            black_box(l),
-                            black_box(serial_cutoff),
-                        )
+                utilization::measure(|| {
+                    old_pool.install(|| {
+                        b.iter(|| {
+                            perf::measure(|| {
+                                map_reduce_fib::<ParallelOldRayon>(
+                                    black_box(&mut input),
+                                    black_box(l),
+                                    black_box(serial_cutoff),
+                                )
+                            })
+                        })
                     })
                 })
             },
         );
+        perf::report(format!("Old Rayon/{}", params));
+        utilization::save("Old vs New Rayon", "Old Rayon", &params);
 
         drop(old_pool);
 
-        let new_pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(cores)
-            .stack_size(STACK_SIZE_MB * 1024 * 1024)
+        let new_pool = ThreadPoolConfig::new()
+            .threads(cores)
+            .placement(affinity::placement_from_env())
+            .stack_size(StackSize::Mb(STACK_SIZE_MB))
             .build()
             .unwrap();
 
         // New Rayon benchmark
         bench_group.bench_with_input(
-            BenchmarkId::new(
-                "New Rayon",
-                param_string(LEN, LATENCY_MS, cores, (fib_n, serial_cutoff)),
-            ),
+            BenchmarkId::new("New Rayon", &params),
             &LATENCY_MS,
             |b, &l| {
-                new_pool.install(|| {
-                    b.iter(|| {
-                        map_reduce_fib::<Parallel>(
-                            black_box(&mut input),
-                            black_box(l),
-                            black_box(serial_cutoff),
-                        )
+                utilization::measure(|| {
+                    new_pool.install(|| {
+                        b.iter(|| {
+                            perf::measure(|| {
+                                map_reduce_fib::<Parallel>(
+                                    black_box(&mut input),
+                                    black_box(l),
+                                    black_box(serial_cutoff),
+                                )
+                            })
+                        })
                     })
                 })
             },
         );
+        perf::report(format!("New Rayon/{}", params));
+        utilization::save("Old vs New Rayon", "New Rayon", &params);
     }
 
     bench_group.finish();
@@ -1,5 +1,6 @@
 use benchmarks::fib::fib;
-use benchmarks::{Parallel, ParallelLH, Serial, Work};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{metadata, perf, utilization, Parallel, ParallelLH, Serial, StackSize, Work};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
 const FIB_N: u32 = 14;
@@ -15,7 +16,10 @@ struct Params(Work);
 impl std::fmt::Display for Params {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Params(Work::DoNothing) | Params(Work::PureLatency { .. }) => {
+            Params(Work::DoNothing)
+            | Params(Work::PureLatency { .. })
+            | Params(Work::RemoteRequest { .. })
+            | Params(Work::QueuedService { .. }) => {
                 panic!("Should not happen during param sweep benching")
             }
             Params(Work::LatencyOrCompute { work_ms, latency_p }) => {
@@ -27,10 +31,14 @@ impl std::fmt::Display for Params {
 
 fn param_sweep(c: &mut Criterion) {
     let mut bench_group = c.benchmark_group("Fib Parameter Sweep");
+    metadata::write_criterion_metadata("Fib Parameter Sweep");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
 
     // Use all cores available
-    rayon::ThreadPoolBuilder::new()
-        .stack_size(STACK_SIZE_MB * 1024 * 1024)
+    ThreadPoolConfig::new()
+        .stack_size(StackSize::Mb(STACK_SIZE_MB))
         .build_global()
         .unwrap();
 
@@ -42,24 +50,33 @@ fn param_sweep(c: &mut Criterion) {
         // Serial benchmark
         bench_group.bench_with_input(BenchmarkId::new("Serial", params), &params, |b, p| {
             b.iter(|| {
-                fib::<Serial>(
-                    black_box(FIB_N),
-                    black_box(&p.0),
-                    black_box(FIB_SERIAL_CUTOFF),
-                )
+                perf::measure(|| {
+                    fib::<Serial>(
+                        black_box(FIB_N),
+                        black_box(&p.0),
+                        black_box(FIB_SERIAL_CUTOFF),
+                    )
+                })
             })
         });
+        perf::report(format!("Serial/{}", params));
 
         // Parallel benchmarks
         bench_group.bench_with_input(BenchmarkId::new("Classic", params), &params, |b, p| {
-            b.iter(|| {
-                fib::<Parallel>(
-                    black_box(FIB_N),
-                    black_box(&p.0),
-                    black_box(FIB_SERIAL_CUTOFF),
-                )
+            utilization::measure(|| {
+                b.iter(|| {
+                    perf::measure(|| {
+                        fib::<Parallel>(
+                            black_box(FIB_N),
+                            black_box(&p.0),
+                            black_box(FIB_SERIAL_CUTOFF),
+                        )
+                    })
+                })
             })
         });
+        perf::report(format!("Classic/{}", params));
+        utilization::save("Fib Parameter Sweep", "Classic", &params.to_string());
 
         for latency_p in LATENCY_P {
             let params = Params(Work::new(work_ms, Some(latency_p)));
@@ -68,15 +85,21 @@ fn param_sweep(c: &mut Criterion) {
                 BenchmarkId::new("Latency Hiding", params),
                 &params,
                 |b, p| {
-                    b.iter(|| {
-                        fib::<ParallelLH>(
-                            black_box(FIB_N),
-                            black_box(&p.0),
-                            black_box(FIB_SERIAL_CUTOFF),
-                        )
+                    utilization::measure(|| {
+                        b.iter(|| {
+                            perf::measure(|| {
+                                fib::<ParallelLH>(
+                                    black_box(FIB_N),
+                                    black_box(&p.0),
+                                    black_box(FIB_SERIAL_CUTOFF),
+                                )
+                            })
+                        })
                     })
                 },
             );
+            perf::report(format!("Latency Hiding/{}", params));
+            utilization::save("Fib Parameter Sweep", "Latency Hiding", &params.to_string());
         }
     }
 
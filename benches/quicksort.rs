@@ -1,30 +1,52 @@
-use benchmarks::quicksort::{generate_random_sequence, quicksort};
-use benchmarks::{Parallel, ParallelLH, Serial, Work};
+use benchmarks::quicksort::{
+    generate_random_elements, generate_random_remote_keys, quicksort, quicksort_remote_keys,
+    ElementType, LargeElement, RandomElement,
+};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    affinity, metadata, perf, utilization, Parallel, ParallelLH, Serial, StackSize, Work,
+};
+use criterion::measurement::WallTime;
 use criterion::BatchSize::SmallInput;
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion,
+};
 
 const STACK_SIZE_MB: usize = 32; // set a large stack size to avoid overflow
 const LATENCY_MS: [Option<u64>; 4] = [None, Some(1), Some(50), Some(100)];
 const LEN: [usize; 1] = [10_000_000];
+const HEAVY_LEN: [usize; 1] = [2_000_000]; // heavyweight elements take up much more memory
+const REMOTE_KEYS_LEN: usize = 1_000_000;
+const REMOTE_KEYS_LATENCY_MS: u64 = 1;
+const REMOTE_KEYS_LATENCY_P: [f32; 3] = [0.0, 0.001, 0.005]; // every key is fetched exactly once
 
-fn inputs() -> Vec<Vec<i32>> {
-    LEN.map(|len| generate_random_sequence(len))
-        .into_iter()
+fn inputs<T: RandomElement>(lens: &[usize]) -> Vec<Vec<T>> {
+    lens.iter()
+        .map(|&len| generate_random_elements::<T>(len))
         .collect()
 }
 
-fn param_string(length: usize, latency_ms: Option<u64>, cores: usize) -> String {
+fn param_string(
+    element: ElementType,
+    length: usize,
+    latency_ms: Option<u64>,
+    cores: usize,
+) -> String {
     format!(
-        "Length: {} | Latency ms: {} | Cores: {}",
+        "Element: {} | Length: {} | Latency ms: {} | Cores: {}",
+        element,
         length,
         latency_ms.unwrap_or(0),
         cores
     )
 }
 
-fn quicksort_bench(c: &mut Criterion) {
-    let mut bench_group = c.benchmark_group("Quicksort");
-    let mut all_inputs = inputs();
+fn bench_element<T: RandomElement>(
+    bench_group: &mut BenchmarkGroup<WallTime>,
+    element: ElementType,
+    lens: &[usize],
+) {
+    let mut all_inputs = inputs::<T>(lens);
 
     let num_cores = {
         let step = if num_cpus::get() <= 10 { 2 } else { 10 };
@@ -36,75 +58,261 @@ fn quicksort_bench(c: &mut Criterion) {
         for latency_ms in LATENCY_MS {
             // Serial benchmark
             bench_group.bench_with_input(
-                BenchmarkId::new("Serial", param_string(input.len(), latency_ms, 1)),
+                BenchmarkId::new("Serial", param_string(element, input.len(), latency_ms, 1)),
                 input,
                 |b, ii| {
                     b.iter_batched_ref(
                         || ii.clone(),
-                        |i| quicksort::<Serial, _>(black_box(i), &Work::new(latency_ms, None)),
+                        |i| {
+                            perf::measure(|| {
+                                quicksort::<Serial, _>(black_box(i), &Work::new(latency_ms, None))
+                            })
+                        },
                         SmallInput,
                     );
                 },
             );
+            perf::report(format!(
+                "Serial/{}",
+                param_string(element, input.len(), latency_ms, 1)
+            ));
 
             // Parallel Benchmarks
             // Setting up and tearing down threadpool in inner loop, but whatever
             for cores in num_cores.clone() {
-                let pool = rayon::ThreadPoolBuilder::new()
-                    .num_threads(cores)
-                    .stack_size(STACK_SIZE_MB * 1024 * 1024)
+                let pool = ThreadPoolConfig::new()
+                    .threads(cores)
+                    .placement(affinity::placement_from_env())
+                    .stack_size(StackSize::Mb(STACK_SIZE_MB))
                     .build()
                     .unwrap();
 
                 bench_group.bench_with_input(
-                    BenchmarkId::new("Classic", param_string(input.len(), latency_ms, cores)),
+                    BenchmarkId::new(
+                        "Classic",
+                        param_string(element, input.len(), latency_ms, cores),
+                    ),
                     input,
                     |b, ii| {
                         b.iter_batched_ref(
                             || ii.clone(),
                             |i| {
-                                pool.install(|| {
-                                    quicksort::<Parallel, _>(
-                                        black_box(i),
-                                        black_box(&Work::new(latency_ms, None)),
-                                    )
+                                perf::measure(|| {
+                                    utilization::measure(|| {
+                                        pool.install(|| {
+                                            quicksort::<Parallel, _>(
+                                                black_box(i),
+                                                black_box(&Work::new(latency_ms, None)),
+                                            )
+                                        })
+                                    })
                                 })
                             },
                             SmallInput,
                         );
                     },
                 );
+                perf::report(format!(
+                    "Classic/{}",
+                    param_string(element, input.len(), latency_ms, cores)
+                ));
+                utilization::save(
+                    "Quicksort",
+                    "Classic",
+                    &param_string(element, input.len(), latency_ms, cores),
+                );
 
                 bench_group.bench_with_input(
                     BenchmarkId::new(
                         "Latency Hiding",
-                        param_string(input.len(), latency_ms, cores),
+                        param_string(element, input.len(), latency_ms, cores),
                     ),
                     input,
                     |b, ii| {
                         b.iter_batched_ref(
                             || ii.clone(),
                             |i| {
-                                pool.install(|| {
-                                    quicksort::<ParallelLH, _>(
-                                        black_box(i),
-                                        black_box(&Work::new(latency_ms, None)),
-                                    )
+                                perf::measure(|| {
+                                    utilization::measure(|| {
+                                        pool.install(|| {
+                                            quicksort::<ParallelLH, _>(
+                                                black_box(i),
+                                                black_box(&Work::new(latency_ms, None)),
+                                            )
+                                        })
+                                    })
                                 })
                             },
                             SmallInput,
                         );
                     },
                 );
+                perf::report(format!(
+                    "Latency Hiding/{}",
+                    param_string(element, input.len(), latency_ms, cores)
+                ));
+                utilization::save(
+                    "Quicksort",
+                    "Latency Hiding",
+                    &param_string(element, input.len(), latency_ms, cores),
+                );
             }
         }
     }
 }
 
+fn quicksort_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Quicksort");
+    metadata::write_criterion_metadata("Quicksort");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    bench_element::<i32>(&mut bench_group, ElementType::I32, &LEN);
+    bench_element::<String>(&mut bench_group, ElementType::String, &HEAVY_LEN);
+    bench_element::<LargeElement>(&mut bench_group, ElementType::Large, &HEAVY_LEN);
+    bench_element::<Box<i32>>(&mut bench_group, ElementType::Boxed, &HEAVY_LEN);
+
+    bench_group.finish();
+}
+
+fn remote_keys_param_string(length: usize, latency_p: f32, cores: usize) -> String {
+    format!(
+        "Length: {} | Latency ms: {} | Latency p: {} | Cores: {}",
+        length, REMOTE_KEYS_LATENCY_MS, latency_p, cores
+    )
+}
+
+fn quicksort_remote_keys_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Quicksort Remote Keys");
+    metadata::write_criterion_metadata("Quicksort Remote Keys");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let input = generate_random_remote_keys::<i32>(REMOTE_KEYS_LEN);
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 10 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for latency_p in REMOTE_KEYS_LATENCY_P {
+        let work = Work::new(Some(REMOTE_KEYS_LATENCY_MS), Some(latency_p));
+
+        // Serial benchmark
+        bench_group.bench_with_input(
+            BenchmarkId::new(
+                "Serial",
+                remote_keys_param_string(input.len(), latency_p, 1),
+            ),
+            &input,
+            |b, ii| {
+                b.iter_batched_ref(
+                    || ii.clone(),
+                    |i| {
+                        perf::measure(|| {
+                            quicksort_remote_keys::<Serial, _>(black_box(i), black_box(&work))
+                        })
+                    },
+                    SmallInput,
+                );
+            },
+        );
+        perf::report(format!(
+            "Serial/{}",
+            remote_keys_param_string(input.len(), latency_p, 1)
+        ));
+
+        // Parallel Benchmarks
+        for cores in num_cores.clone() {
+            let pool = ThreadPoolConfig::new()
+                .threads(cores)
+                .placement(affinity::placement_from_env())
+                .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                .build()
+                .unwrap();
+
+            bench_group.bench_with_input(
+                BenchmarkId::new(
+                    "Classic",
+                    remote_keys_param_string(input.len(), latency_p, cores),
+                ),
+                &input,
+                |b, ii| {
+                    b.iter_batched_ref(
+                        || ii.clone(),
+                        |i| {
+                            perf::measure(|| {
+                                utilization::measure(|| {
+                                    pool.install(|| {
+                                        quicksort_remote_keys::<Parallel, _>(
+                                            black_box(i),
+                                            black_box(&work),
+                                        )
+                                    })
+                                })
+                            })
+                        },
+                        SmallInput,
+                    );
+                },
+            );
+            perf::report(format!(
+                "Classic/{}",
+                remote_keys_param_string(input.len(), latency_p, cores)
+            ));
+            utilization::save(
+                "Quicksort Remote Keys",
+                "Classic",
+                &remote_keys_param_string(input.len(), latency_p, cores),
+            );
+
+            bench_group.bench_with_input(
+                BenchmarkId::new(
+                    "Latency Hiding",
+                    remote_keys_param_string(input.len(), latency_p, cores),
+                ),
+                &input,
+                |b, ii| {
+                    b.iter_batched_ref(
+                        || ii.clone(),
+                        |i| {
+                            perf::measure(|| {
+                                utilization::measure(|| {
+                                    pool.install(|| {
+                                        quicksort_remote_keys::<ParallelLH, _>(
+                                            black_box(i),
+                                            black_box(&work),
+                                        )
+                                    })
+                                })
+                            })
+                        },
+                        SmallInput,
+                    );
+                },
+            );
+            perf::report(format!(
+                "Latency Hiding/{}",
+                remote_keys_param_string(input.len(), latency_p, cores)
+            ));
+            utilization::save(
+                "Quicksort Remote Keys",
+                "Latency Hiding",
+                &remote_keys_param_string(input.len(), latency_p, cores),
+            );
+        }
+    }
+
+    bench_group.finish();
+}
+
 criterion_group! {
   name = benches;
   // config = Criterion::default().sample_size(35);
   config = Criterion::default().sample_size(10);
-  targets = quicksort_bench
+  targets = quicksort_bench, quicksort_remote_keys_bench
 }
 criterion_main!(benches);
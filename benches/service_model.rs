@@ -0,0 +1,124 @@
+use benchmarks::fib::fib;
+use benchmarks::service_model::ServiceModel;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{affinity, metadata, perf, utilization, Parallel, ParallelLH, StackSize, Work};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const FIB_N: u32 = 14;
+const FIB_SERIAL_CUTOFF: u32 = 0; // needs to be 0 so every leaf sends a request
+
+const STACK_SIZE_MB: usize = 16; // set a large stack size to avoid overflow
+const SERVICE_MS: u64 = 10;
+const SERVERS: [usize; 4] = [1, 4, 16, 64];
+const QUEUE_CAPACITY: usize = 256;
+
+fn param_string(servers: usize, cores: usize) -> String {
+    format!(
+        "Service ms: {} | Servers: {} | Queue: {} | Cores: {}",
+        SERVICE_MS, servers, QUEUE_CAPACITY, cores
+    )
+}
+
+fn service_model_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("Service Model Fib");
+    metadata::write_criterion_metadata("Service Model Fib");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for servers in SERVERS {
+        // Separate models for both schedulers, so their queueing stats can be compared
+        let classic_model = Box::leak(Box::new(ServiceModel::new(
+            servers,
+            QUEUE_CAPACITY,
+            SERVICE_MS,
+        )));
+        let lh_model = Box::leak(Box::new(ServiceModel::new(
+            servers,
+            QUEUE_CAPACITY,
+            SERVICE_MS,
+        )));
+
+        // Setting up and tearing down threadpool in inner loop, but whatever
+        for cores in num_cores.clone() {
+            let pool = ThreadPoolConfig::new()
+                .threads(cores)
+                .placement(affinity::placement_from_env())
+                .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                .build()
+                .unwrap();
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Classic", param_string(servers, cores)),
+                &Work::QueuedService {
+                    model: classic_model,
+                },
+                |b, w| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    fib::<Parallel>(
+                                        black_box(FIB_N),
+                                        black_box(w),
+                                        black_box(FIB_SERIAL_CUTOFF),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Classic/{}", param_string(servers, cores)));
+            utilization::save(
+                "Service Model Fib",
+                "Classic",
+                &param_string(servers, cores),
+            );
+
+            bench_group.bench_with_input(
+                BenchmarkId::new("Latency Hiding", param_string(servers, cores)),
+                &Work::QueuedService { model: lh_model },
+                |b, w| {
+                    utilization::measure(|| {
+                        pool.install(|| {
+                            b.iter(|| {
+                                perf::measure(|| {
+                                    fib::<ParallelLH>(
+                                        black_box(FIB_N),
+                                        black_box(w),
+                                        black_box(FIB_SERIAL_CUTOFF),
+                                    )
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Latency Hiding/{}", param_string(servers, cores)));
+            utilization::save(
+                "Service Model Fib",
+                "Latency Hiding",
+                &param_string(servers, cores),
+            );
+        }
+
+        println!("Classic, {} servers: {}", servers, classic_model.stats());
+        println!("Latency Hiding, {} servers: {}", servers, lh_model.stats());
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = service_model_bench
+}
+criterion_main!(benches);
@@ -0,0 +1,141 @@
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::uts::{uts, TreeShape};
+use benchmarks::{
+    affinity, metadata, perf, utilization, Parallel, ParallelLH, Serial, StackSize, Work,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const STACK_SIZE_MB: usize = 32; // set a large stack size to avoid overflow, binomial trees get deep
+const LATENCY_MS: [Option<u64>; 3] = [None, Some(1), Some(10)];
+const SEED: u64 = 19;
+const TREES: [(&str, TreeShape); 2] = [
+    (
+        "Geometric",
+        TreeShape::Geometric {
+            branching_factor: 4.0,
+            max_depth: 6,
+        },
+    ),
+    (
+        "Binomial",
+        TreeShape::Binomial {
+            root_children: 100,
+            q: 0.12,
+            m: 8,
+        },
+    ),
+];
+
+fn param_string(tree: &str, latency_ms: Option<u64>, cores: usize) -> String {
+    format!(
+        "Tree: {} | Latency ms: {} | Cores: {}",
+        tree,
+        latency_ms.unwrap_or(0),
+        cores
+    )
+}
+
+fn uts_bench(c: &mut Criterion) {
+    let mut bench_group = c.benchmark_group("UTS");
+    metadata::write_criterion_metadata("UTS");
+
+    // Before building any threadpool, so workers inherit the counters
+    perf::open_counters_from_env();
+
+    let num_cores = {
+        let step = if num_cpus::get() <= 10 { 2 } else { 5 };
+        [1].into_iter()
+            .chain((step..=num_cpus::get()).step_by(step))
+    };
+
+    for (tree, shape) in TREES {
+        for latency_ms in LATENCY_MS {
+            let work = Work::new(latency_ms, None);
+
+            // Serial benchmark
+            bench_group.bench_with_input(
+                BenchmarkId::new("Serial", param_string(tree, latency_ms, 1)),
+                &work,
+                |b, w| {
+                    b.iter(|| {
+                        perf::measure(|| {
+                            uts::<Serial>(black_box(&shape), black_box(SEED), black_box(w))
+                        })
+                    })
+                },
+            );
+            perf::report(format!("Serial/{}", param_string(tree, latency_ms, 1)));
+
+            // Parallel benchmarks
+            // Setting up and tearing down threadpool in inner loop, but whatever
+            for cores in num_cores.clone() {
+                let pool = ThreadPoolConfig::new()
+                    .threads(cores)
+                    .placement(affinity::placement_from_env())
+                    .stack_size(StackSize::Mb(STACK_SIZE_MB))
+                    .build()
+                    .unwrap();
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new("Classic", param_string(tree, latency_ms, cores)),
+                    &work,
+                    |b, w| {
+                        utilization::measure(|| {
+                            pool.install(|| {
+                                b.iter(|| {
+                                    perf::measure(|| {
+                                        uts::<Parallel>(
+                                            black_box(&shape),
+                                            black_box(SEED),
+                                            black_box(w),
+                                        )
+                                    })
+                                })
+                            })
+                        })
+                    },
+                );
+                perf::report(format!("Classic/{}", param_string(tree, latency_ms, cores)));
+                utilization::save("UTS", "Classic", &param_string(tree, latency_ms, cores));
+
+                bench_group.bench_with_input(
+                    BenchmarkId::new("Latency Hiding", param_string(tree, latency_ms, cores)),
+                    &work,
+                    |b, w| {
+                        utilization::measure(|| {
+                            pool.install(|| {
+                                b.iter(|| {
+                                    perf::measure(|| {
+                                        uts::<ParallelLH>(
+                                            black_box(&shape),
+                                            black_box(SEED),
+                                            black_box(w),
+                                        )
+                                    })
+                                })
+                            })
+                        })
+                    },
+                );
+                perf::report(format!(
+                    "Latency Hiding/{}",
+                    param_string(tree, latency_ms, cores)
+                ));
+                utilization::save(
+                    "UTS",
+                    "Latency Hiding",
+                    &param_string(tree, latency_ms, cores),
+                );
+            }
+        }
+    }
+
+    bench_group.finish();
+}
+
+criterion_group! {
+  name = benches;
+  config = Criterion::default().sample_size(10);
+  targets = uts_bench
+}
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Revision of the git checkout at `dir`, suffixed with "-dirty" if it has uncommitted changes.
+fn git_revision(dir: &Path) -> Option<String> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    let revision = git(&["rev-parse", "HEAD"])?;
+    let dirty = !git(&["status", "--porcelain", "--untracked-files=no"])?.is_empty();
+
+    Some(if dirty {
+        format!("{}-dirty", revision)
+    } else {
+        revision
+    })
+}
+
+fn rerun_if_changed(path: &Path) {
+    // Cargo reruns the build script on every build for paths that don't exist
+    if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+fn main() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // The patched rayon the crate is built against, see `[patch.crates-io]` in Cargo.toml
+    let rayon_dir = manifest_dir.join("../rayon");
+
+    for dir in [manifest_dir, rayon_dir.as_path()] {
+        rerun_if_changed(&dir.join(".git/HEAD"));
+        rerun_if_changed(&dir.join(".git/index"));
+    }
+    rerun_if_changed(&rayon_dir.join("src"));
+    rerun_if_changed(&rayon_dir.join("rayon-core/src"));
+    rerun_if_changed(&manifest_dir.join("src"));
+    rerun_if_changed(&manifest_dir.join("benches"));
+
+    let unknown = || "unknown".to_string();
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(unknown);
+
+    println!(
+        "cargo:rustc-env=BENCHMARKS_REVISION={}",
+        git_revision(manifest_dir).unwrap_or_else(unknown)
+    );
+    println!(
+        "cargo:rustc-env=RAYON_REVISION={}",
+        git_revision(&rayon_dir).unwrap_or_else(unknown)
+    );
+    println!("cargo:rustc-env=RUSTC_VERSION={}", rustc_version);
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_else(|_| unknown())
+    );
+    println!(
+        "cargo:rustc-env=BUILD_OPT_LEVEL={}",
+        std::env::var("OPT_LEVEL").unwrap_or_else(|_| unknown())
+    );
+}
@@ -13,7 +13,7 @@ schedulers = map(lambda s: Path(os.path.join(bench_group, s)), ['Serial', 'Class
 
 data = [] # list of observation dict rows to be put into a pandas df
 
-for scheduler_path in filter(lambda dir_entry: dir_entry.name != 'report', os.scandir(bench_group)):
+for scheduler_path in filter(lambda dir_entry: dir_entry.is_dir() and dir_entry.name != 'report', os.scandir(bench_group)):
     for bench in filter(lambda dir_entry: dir_entry.name != 'report', os.scandir(scheduler_path)):
         observation = {} # row in pandas df
         observation['Scheduler'] = scheduler_path.name
@@ -41,17 +41,13 @@ for scheduler_path in filter(lambda dir_entry: dir_entry.name != 'report', os.sc
 
 df = pd.DataFrame(data)
 
-# Append rows for Ideal scheduler
-ideal = df.loc[((df['Scheduler'] == 'Classic') & (df['Latency ms'] == 0))]
-ideal.loc[:, 'Scheduler'] = 'Ideal'
-df = pd.concat([df, ideal], ignore_index=True)
-
 print(df, end='\n---------------\n')
 
 latencies = df.loc[(df['Latency ms'] != 0), 'Latency ms'].unique() # for each latency graph, don't include 0 ms
 
 for latency in latencies:
-    latency_view = df.loc[((df['Latency ms'] == latency) | (df['Scheduler'] == 'Ideal'))]
+    # The plot binary draws the model's predicted speedup next to the measured ones
+    latency_view = df.loc[df['Latency ms'] == latency]
 
     serial_baseline = latency_view.loc[(latency_view['Scheduler'] == 'Serial'), 'Wallclock']
     assert serial_baseline.size == 1
@@ -59,14 +55,12 @@ for latency in latencies:
 
     latency_view.loc[:, 'Speedup'] = serial_baseline / latency_view.loc[:, 'Wallclock']
 
-    ideal = latency_view.loc[latency_view['Scheduler'] == 'Ideal', ['Cores', 'Speedup']].sort_values(by=['Cores'])
     classic = latency_view.loc[latency_view['Scheduler'] == 'Classic', ['Cores', 'Speedup']].sort_values(by=['Cores'])
     lh = latency_view.loc[latency_view['Scheduler'] == 'Latency Hiding', ['Cores', 'Speedup']].sort_values(by=['Cores'])
 
     with sns.axes_style(style="whitegrid"):
         plt.plot(classic['Cores'], classic['Speedup'], marker='D', label='Classic')
         plt.plot(lh['Cores'], lh['Speedup'], marker='^', label='ProWS-R')
-        plt.plot(ideal['Cores'], ideal['Speedup'], marker='o', label='Ideal')
 
         plt.title(f'MapReduceFib with Latency: {latency}ms')
         plt.legend(loc='best')
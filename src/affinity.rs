@@ -0,0 +1,160 @@
+use clap::ArgEnum;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+/// Benches pin workers with this placement, "compact" or "scatter", if it is set.
+pub const PLACEMENT_ENV: &str = "PLACEMENT";
+
+const SYS_CPU: &str = "/sys/devices/system/cpu";
+
+/// CPU of every pinned worker by worker index, for the most recently built pool
+static PINNED: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum Placement {
+    /// Fill up SMT siblings, then cores of a package, before moving on to the next package
+    Compact,
+    /// Spread out over packages and physical cores first, use SMT siblings last
+    Scatter,
+}
+
+impl std::fmt::Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Placement::Compact => write!(f, "Compact"),
+            Placement::Scatter => write!(f, "Scatter"),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Cpu {
+    pub id: usize,
+    pub core_id: usize,
+    pub package_id: usize,
+}
+
+fn read_topology(cpu: usize, file: &str) -> io::Result<usize> {
+    let path = format!("{}/cpu{}/topology/{}", SYS_CPU, cpu, file);
+
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// CPUs the process is allowed to run on, with their topology.
+pub fn cpus() -> io::Result<Vec<Cpu>> {
+    let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+    if unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    (0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .map(|id| {
+            Ok(Cpu {
+                id,
+                core_id: read_topology(id, "core_id")?,
+                package_id: read_topology(id, "physical_package_id")?,
+            })
+        })
+        .collect()
+}
+
+/// Order in which workers are assigned to CPUs.
+pub fn cpu_order(placement: Placement) -> io::Result<Vec<usize>> {
+    let mut cpus = cpus()?;
+    cpus.sort_by_key(|cpu| (cpu.package_id, cpu.core_id, cpu.id));
+
+    match placement {
+        Placement::Compact => Ok(cpus.iter().map(|cpu| cpu.id).collect()),
+        Placement::Scatter => {
+            // Rank of each CPU among its SMT siblings, and of its core within its package
+            let mut ranked = Vec::with_capacity(cpus.len());
+            let (mut sibling_rank, mut core_rank) = (0, 0);
+
+            for (i, cpu) in cpus.iter().enumerate() {
+                match i.checked_sub(1).map(|j| &cpus[j]) {
+                    Some(prev) if prev.package_id == cpu.package_id => {
+                        if prev.core_id == cpu.core_id {
+                            sibling_rank += 1;
+                        } else {
+                            sibling_rank = 0;
+                            core_rank += 1;
+                        }
+                    }
+                    _ => {
+                        sibling_rank = 0;
+                        core_rank = 0;
+                    }
+                }
+
+                ranked.push((sibling_rank, core_rank, cpu.package_id, cpu.id));
+            }
+
+            ranked.sort_unstable();
+            Ok(ranked.into_iter().map(|(.., id)| id).collect())
+        }
+    }
+}
+
+/// Placement from `PLACEMENT` in the environment. An invalid value is reported once and otherwise
+/// ignored, so benches still run.
+pub fn placement_from_env() -> Option<Placement> {
+    static PLACEMENT: OnceLock<Option<Placement>> = OnceLock::new();
+
+    *PLACEMENT.get_or_init(|| {
+        let value = std::env::var(PLACEMENT_ENV).ok()?;
+
+        Placement::from_str(&value, true)
+            .map_err(|e| eprintln!("Ignoring {}={}: {}", PLACEMENT_ENV, value, e))
+            .ok()
+    })
+}
+
+/// Forgets the workers pinned so far, called whenever a pool is built so `worker_cpus` only
+/// reports the workers of the newest pool.
+pub(crate) fn forget_pinned() {
+    PINNED.lock().unwrap().clear();
+}
+
+/// Start handler for worker threads, pins the current thread to its CPU in `cpu_order`. Workers
+/// beyond the number of CPUs wrap around.
+pub(crate) fn pin_current_thread(cpu_order: &[usize], thread_index: usize) {
+    if cpu_order.is_empty() {
+        return;
+    }
+
+    let cpu = cpu_order[thread_index % cpu_order.len()];
+
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+        libc::CPU_SET(cpu, &mut set);
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            eprintln!(
+                "Could not pin worker {} to CPU {}: {}",
+                thread_index,
+                cpu,
+                io::Error::last_os_error()
+            );
+            return;
+        }
+    }
+
+    PINNED.lock().unwrap().insert(thread_index, cpu);
+}
+
+/// (worker index, CPU) of every worker pinned so far, by worker index.
+pub fn worker_cpus() -> Vec<(usize, usize)> {
+    PINNED
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(&worker, &cpu)| (worker, cpu))
+        .collect()
+}
@@ -0,0 +1,579 @@
+use crate::utilization::{Utilization, UTILIZATION_FILE};
+use clap::ArgEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Parameter all benches use for the number of worker threads.
+pub const CORES_PARAM: &str = "Cores";
+/// Parameter of benches sweeping over simulated latency.
+pub const LATENCY_PARAM: &str = "Latency ms";
+/// Directory criterion saves the latest run of a benchmark to.
+pub const LATEST_RUN: &str = "new";
+/// Benchmark id of serial runs, the default T1 for speedups.
+pub const SERIAL: &str = "Serial";
+/// Benchmark id of runs using the classic, blocking joiner.
+pub const CLASSIC: &str = "Classic";
+/// Benchmark id of runs using the latency hiding joiner.
+pub const LATENCY_HIDING: &str = "Latency Hiding";
+/// Benchmark id of runs using the unpatched rayon.
+pub const OLD_RAYON: &str = "Old Rayon";
+
+/// Parameters of a benchmark, parsed from a criterion value string like
+/// "Latency ms: 1 | Cores: 4". Keeps the order of the value string.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Params(pub Vec<(String, String)>);
+
+/// Compares parameter values as numbers if both are numeric, so "10" comes after "2".
+pub fn cmp_values(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y).then_with(|| a.cmp(b)),
+        _ => a.cmp(b),
+    }
+}
+
+impl Ord for Params {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|((a_name, a), (b_name, b))| a_name.cmp(b_name).then_with(|| cmp_values(a, b)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+impl PartialOrd for Params {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Params {
+    pub fn parse(value_str: &str) -> Self {
+        Params(
+            value_str
+                .split('|')
+                .filter_map(|param| {
+                    let (name, value) = param.split_once(':')?;
+                    Some((name.trim().to_string(), value.trim().to_string()))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn cores(&self) -> Option<usize> {
+        self.get(CORES_PARAM)?.parse().ok()
+    }
+
+    pub fn without(&self, name: &str) -> Self {
+        Params(self.0.iter().filter(|(n, _)| n != name).cloned().collect())
+    }
+
+    /// Same parameters, with the value of `name` replaced if it is present.
+    pub fn with(&self, name: &str, value: &str) -> Self {
+        Params(
+            self.0
+                .iter()
+                .map(|(n, v)| (n.clone(), if n == name { value } else { v }.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl std::fmt::Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, (name, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}: {}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Samples of one benchmark of a criterion group, pooled over all runs that were loaded.
+#[derive(Serialize, Deserialize)]
+pub struct Benchmark {
+    /// Benchmark id within the group, e.g. "Classic" or "Latency Hiding"
+    pub joiner: String,
+    pub params: Params,
+    /// Time per iteration of every sample, in ns
+    pub times_ns: Vec<f64>,
+    /// Worker utilization, if the latest run was loaded and the bench measured it
+    pub utilization: Option<Utilization>,
+}
+
+#[derive(Deserialize)]
+struct BenchmarkInfo {
+    function_id: Option<String>,
+    value_str: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SampleData {
+    iters: Vec<f64>,
+    times: Vec<f64>,
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let json = fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Loads every benchmark of the criterion group in `group_dir`. Each of `runs` names a directory
+/// criterion saved results to ("new" for the latest run, or a name passed to `--save-baseline`),
+/// samples of the same benchmark are pooled over all runs.
+pub fn load_criterion_group(group_dir: &Path, runs: &[String]) -> io::Result<Vec<Benchmark>> {
+    let mut benchmarks: BTreeMap<(String, Params), (Vec<f64>, Option<Utilization>)> =
+        BTreeMap::new();
+    let mut dirs = vec![group_dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str());
+
+            if !path.is_dir() || name == Some("report") {
+                continue;
+            }
+
+            if !name.is_some_and(|name| runs.iter().any(|run| run == name)) {
+                dirs.push(path);
+                continue;
+            }
+
+            let info: BenchmarkInfo = read_json(&path.join("benchmark.json"))?;
+            let samples: SampleData = read_json(&path.join("sample.json"))?;
+
+            let key = (
+                info.function_id.unwrap_or_default(),
+                Params::parse(info.value_str.as_deref().unwrap_or_default()),
+            );
+            let times = samples
+                .times
+                .iter()
+                .zip(&samples.iters)
+                .map(|(time, iters)| time / iters);
+
+            // Benches only keep the utilization of the latest run
+            let utilization = match name {
+                Some(LATEST_RUN) => Utilization::read_json(&dir.join(UTILIZATION_FILE)).ok(),
+                _ => None,
+            };
+
+            let (times_ns, benchmark_utilization) = benchmarks.entry(key).or_default();
+            times_ns.extend(times);
+            *benchmark_utilization = benchmark_utilization.take().or(utilization);
+        }
+    }
+
+    Ok(benchmarks
+        .into_iter()
+        .map(|((joiner, params), (times_ns, utilization))| Benchmark {
+            joiner,
+            params,
+            times_ns,
+            utilization,
+        })
+        .collect())
+}
+
+/// Point estimate with a confidence interval.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Estimate {
+    pub point: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Estimate {
+    pub fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        Estimate {
+            point: f(self.point),
+            low: f(self.low),
+            high: f(self.high),
+        }
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.3} [{:.3}, {:.3}]", self.point, self.low, self.high)
+    }
+}
+
+pub fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+#[derive(Debug)]
+pub enum ParseConfidenceError {
+    OutOfBounds,
+    ParseError,
+}
+
+impl std::error::Error for ParseConfidenceError {}
+
+impl std::fmt::Display for ParseConfidenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseConfidenceError::OutOfBounds => {
+                write!(f, "Confidence not in range (0.0, 1.0)")
+            }
+            ParseConfidenceError::ParseError => {
+                write!(f, "Argument for confidence could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_confidence(s: &str) -> Result<f64, ParseConfidenceError> {
+    match s.parse::<f64>() {
+        Ok(confidence) if confidence > 0.0 && confidence < 1.0 => Ok(confidence),
+        Ok(_) => Err(ParseConfidenceError::OutOfBounds),
+        Err(_) => Err(ParseConfidenceError::ParseError),
+    }
+}
+
+/// Percentile bootstrap over the means of independent sample sets.
+pub struct Bootstrap {
+    resamples: usize,
+    /// In (0.0, 1.0)
+    confidence: f64,
+    rng: StdRng,
+}
+
+impl Bootstrap {
+    pub fn new(resamples: usize, confidence: f64, seed: u64) -> Self {
+        assert!(
+            confidence > 0.0 && confidence < 1.0,
+            "Confidence must be in (0.0, 1.0)"
+        );
+
+        Bootstrap {
+            resamples,
+            confidence,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn resampled_mean(&mut self, samples: &[f64]) -> f64 {
+        let sum: f64 = (0..samples.len())
+            .map(|_| samples[self.rng.gen_range(0..samples.len())])
+            .sum();
+
+        sum / samples.len() as f64
+    }
+
+    fn interval(&self, point: f64, mut resampled: Vec<f64>) -> Estimate {
+        resampled.retain(|value| value.is_finite());
+        if resampled.is_empty() {
+            return Estimate {
+                point,
+                low: point,
+                high: point,
+            };
+        }
+
+        resampled.sort_unstable_by(|x, y| x.partial_cmp(y).unwrap());
+
+        let tail = (1.0 - self.confidence) / 2.0;
+        let at =
+            |q: f64| resampled[((q * resampled.len() as f64) as usize).min(resampled.len() - 1)];
+
+        Estimate {
+            point,
+            low: at(tail),
+            high: at(1.0 - tail),
+        }
+    }
+
+    pub fn mean(&mut self, samples: &[f64]) -> Estimate {
+        let resampled = (0..self.resamples)
+            .map(|_| self.resampled_mean(samples))
+            .collect();
+
+        self.interval(mean(samples), resampled)
+    }
+
+    /// Estimate of `statistic` applied to the means of `a` and `b`.
+    pub fn estimate(
+        &mut self,
+        a: &[f64],
+        b: &[f64],
+        statistic: impl Fn(f64, f64) -> f64,
+    ) -> Estimate {
+        self.estimate_all([a, b], |[a, b]| statistic(a, b))
+    }
+
+    /// Estimate of `statistic` applied to the means of every sample set.
+    pub fn estimate_all<const N: usize>(
+        &mut self,
+        sets: [&[f64]; N],
+        statistic: impl Fn([f64; N]) -> f64,
+    ) -> Estimate {
+        let resampled = (0..self.resamples)
+            .map(|_| statistic(sets.map(|samples| self.resampled_mean(samples))))
+            .collect();
+
+        self.interval(statistic(sets.map(mean)), resampled)
+    }
+}
+
+/// Run whose time is used as T1 for speedups.
+#[derive(Copy, Clone, ArgEnum)]
+pub enum Reference {
+    /// The serial run with the same parameters, or the joiner's own single core run if there is none
+    Serial,
+    /// The joiner's own run on a single core
+    SingleCore,
+}
+
+/// Scaling of one joiner at one core count, relative to T1.
+pub struct ScalingRow {
+    pub joiner: String,
+    /// Parameters other than the core count
+    pub params: Params,
+    pub cores: usize,
+    /// Joiner T1 was measured with
+    pub reference: String,
+    pub samples: usize,
+    /// Time per iteration, in ms
+    pub time_ms: Estimate,
+    /// T1 / TP
+    pub speedup: Estimate,
+    /// Speedup / P
+    pub efficiency: Estimate,
+    /// Experimentally determined serial fraction, undefined on a single core
+    pub karp_flatt: Option<Estimate>,
+}
+
+/// Benchmark of `joiner` whose parameters other than the core count are `params`, on `cores` cores
+/// if given.
+pub fn find<'a>(
+    benchmarks: &'a [Benchmark],
+    joiner: &str,
+    params: &Params,
+    cores: Option<usize>,
+) -> Option<&'a Benchmark> {
+    benchmarks.iter().find(|b| {
+        b.joiner == joiner
+            && b.params.without(CORES_PARAM) == *params
+            && cores.is_none_or(|cores| b.params.cores() == Some(cores))
+            && !b.times_ns.is_empty()
+    })
+}
+
+/// Benchmark to use as T1 for `joiner` with parameters `params`, other than the core count.
+pub fn find_reference<'a>(
+    benchmarks: &'a [Benchmark],
+    joiner: &str,
+    params: &Params,
+    reference: Reference,
+) -> Option<&'a Benchmark> {
+    let own_single_core = || find(benchmarks, joiner, params, Some(1));
+
+    match reference {
+        Reference::Serial => find(benchmarks, SERIAL, params, None).or_else(own_single_core),
+        Reference::SingleCore => own_single_core(),
+    }
+}
+
+/// Speedup, efficiency and Karp–Flatt serial fraction of every parallel benchmark with a core count
+/// parameter, sorted so joiners can be compared at each core count.
+pub fn scaling(
+    benchmarks: &[Benchmark],
+    reference: Reference,
+    bootstrap: &mut Bootstrap,
+) -> Vec<ScalingRow> {
+    let mut rows: Vec<ScalingRow> = benchmarks
+        .iter()
+        .filter(|b| b.joiner != SERIAL && !b.times_ns.is_empty())
+        .filter_map(|b| {
+            let cores = b.params.cores()?;
+            let params = b.params.without(CORES_PARAM);
+            let t1 = find_reference(benchmarks, &b.joiner, &params, reference)?;
+
+            let p = cores as f64;
+            let karp_flatt = (cores > 1).then(|| {
+                bootstrap.estimate(&t1.times_ns, &b.times_ns, |t1, tp| {
+                    (tp / t1 - 1.0 / p) / (1.0 - 1.0 / p)
+                })
+            });
+
+            Some(ScalingRow {
+                joiner: b.joiner.clone(),
+                params,
+                cores,
+                reference: t1.joiner.clone(),
+                samples: b.times_ns.len(),
+                time_ms: bootstrap.mean(&b.times_ns).map(|tp| tp / 1e6),
+                speedup: bootstrap.estimate(&t1.times_ns, &b.times_ns, |t1, tp| t1 / tp),
+                efficiency: bootstrap.estimate(&t1.times_ns, &b.times_ns, |t1, tp| t1 / tp / p),
+                karp_flatt,
+            })
+        })
+        .collect();
+
+    rows.sort_by(|a, b| (&a.params, a.cores, &a.joiner).cmp(&(&b.params, b.cores, &b.joiner)));
+    rows
+}
+
+/// Prints rows as a table, with a header for each set of parameters.
+pub struct ScalingTable<'a>(pub &'a [ScalingRow]);
+
+impl std::fmt::Display for ScalingTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut params = None;
+
+        for row in self.0 {
+            if params != Some(&row.params) {
+                params = Some(&row.params);
+
+                writeln!(f)?;
+                writeln!(f, "{}", row.params)?;
+                writeln!(
+                    f,
+                    "{:<16} {:>5} {:>7} {:<10} {:<34} {:<28} {:<28} {:<10}",
+                    "joiner",
+                    "cores",
+                    "samples",
+                    "T1",
+                    "time ms",
+                    "speedup",
+                    "efficiency",
+                    "karp-flatt"
+                )?;
+            }
+
+            writeln!(
+                f,
+                "{:<16} {:>5} {:>7} {:<10} {:<34} {:<28} {:<28} {}",
+                row.joiner,
+                row.cores,
+                row.samples,
+                row.reference,
+                row.time_ms.to_string(),
+                row.speedup.to_string(),
+                row.efficiency.to_string(),
+                row.karp_flatt
+                    .map_or_else(|| "-".to_string(), |karp_flatt| karp_flatt.to_string())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn benchmark(joiner: &str, params: &str, time_ms: f64) -> Benchmark {
+        Benchmark {
+            joiner: joiner.to_string(),
+            params: Params::parse(params),
+            times_ns: vec![time_ms * 1e6; 10],
+            utilization: None,
+        }
+    }
+
+    fn row<'a>(rows: &'a [ScalingRow], joiner: &str, cores: usize) -> &'a ScalingRow {
+        rows.iter()
+            .find(|row| row.joiner == joiner && row.cores == cores)
+            .unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn scaling_against_serial() {
+        let benchmarks = [
+            benchmark(SERIAL, "Latency ms: 1", 100.0),
+            benchmark(CLASSIC, "Latency ms: 1 | Cores: 1", 110.0),
+            benchmark(CLASSIC, "Latency ms: 1 | Cores: 4", 40.0),
+        ];
+
+        let rows = scaling(
+            &benchmarks,
+            Reference::Serial,
+            &mut Bootstrap::new(100, 0.95, 0),
+        );
+        let row = row(&rows, CLASSIC, 4);
+
+        assert_eq!(row.reference, SERIAL);
+        assert_close(row.speedup.point, 2.5);
+        assert_close(row.efficiency.point, 0.625);
+        // (1/2.5 - 1/4) / (1 - 1/4)
+        assert_close(row.karp_flatt.unwrap().point, 0.2);
+    }
+
+    #[test]
+    fn scaling_against_single_core() {
+        let benchmarks = [
+            benchmark(SERIAL, "Latency ms: 1", 100.0),
+            benchmark(CLASSIC, "Latency ms: 1 | Cores: 1", 120.0),
+            benchmark(CLASSIC, "Latency ms: 1 | Cores: 2", 60.0),
+        ];
+
+        let rows = scaling(
+            &benchmarks,
+            Reference::SingleCore,
+            &mut Bootstrap::new(100, 0.95, 0),
+        );
+
+        let single_core = row(&rows, CLASSIC, 1);
+        assert_close(single_core.efficiency.point, 1.0);
+        // Undefined on a single core
+        assert!(single_core.karp_flatt.is_none());
+
+        let two_cores = row(&rows, CLASSIC, 2);
+        assert_close(two_cores.efficiency.point, 1.0);
+        assert_close(two_cores.karp_flatt.unwrap().point, 0.0);
+    }
+
+    #[test]
+    fn bootstrap_interval_contains_mean() {
+        let samples: Vec<f64> = (0..50).map(|i| 100.0 + (i % 10) as f64).collect();
+
+        let narrow = Bootstrap::new(2000, 0.5, 0).mean(&samples);
+        let wide = Bootstrap::new(2000, 0.99, 0).mean(&samples);
+
+        assert_close(wide.point, 104.5);
+        assert!(narrow.low <= narrow.point && narrow.point <= narrow.high);
+        assert!(wide.low < narrow.low && narrow.high < wide.high);
+        // Means of resamples never leave the range of the samples
+        assert!(100.0 <= wide.low && wide.high <= 109.0);
+    }
+
+    #[test]
+    fn bootstrap_interval_of_constant_samples_is_a_point() {
+        let estimate = Bootstrap::new(100, 0.95, 0).estimate(&[2.0; 5], &[1.0; 5], |a, b| a / b);
+
+        assert_close(estimate.point, 2.0);
+        assert_close(estimate.low, 2.0);
+        assert_close(estimate.high, 2.0);
+    }
+}
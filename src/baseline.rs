@@ -0,0 +1,272 @@
+use crate::analysis::{Benchmark, Bootstrap, Estimate, Params, LATENCY_HIDING, OLD_RAYON, SERIAL};
+use crate::metadata::RunMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory named baselines are saved to, relative to the crate root.
+pub const BASELINE_DIR: &str = "baselines";
+
+/// File a baseline called `name` is saved to.
+pub fn baseline_path(name: &str) -> PathBuf {
+    Path::new(BASELINE_DIR).join(format!("{}.json", name))
+}
+
+/// Results of one criterion group at the time the baseline was saved.
+#[derive(Serialize, Deserialize)]
+pub struct GroupBaseline {
+    pub metadata: Option<RunMetadata>,
+    pub benchmarks: Vec<Benchmark>,
+}
+
+/// Named snapshot of the samples of every benchmark in some criterion groups, for comparing later
+/// runs against, e.g. before upgrading the rayon fork.
+#[derive(Serialize, Deserialize)]
+pub struct Baseline {
+    pub name: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub groups: BTreeMap<String, GroupBaseline>,
+}
+
+impl Baseline {
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_string(self).expect("Baseline is always serializable");
+        fs::write(path, json)
+    }
+
+    pub fn read_json(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Check {
+    /// Time of the latency hiding joiner
+    Time,
+    /// Time of a joiner relative to the unpatched rayon with the same parameters
+    OverheadVsOldRayon,
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Check::Time => write!(f, "time"),
+            Check::OverheadVsOldRayon => write!(f, "overhead vs {}", OLD_RAYON),
+        }
+    }
+}
+
+/// Change of one benchmark between the baseline and the current run.
+pub struct Comparison {
+    pub group: String,
+    pub joiner: String,
+    pub params: Params,
+    pub check: Check,
+    /// Current / baseline of the checked quantity, above 1 is slower
+    pub change: Estimate,
+    /// Whether the whole confidence interval of the change is above the tolerance
+    pub regression: bool,
+}
+
+fn find<'a>(benchmarks: &'a [Benchmark], joiner: &str, params: &Params) -> Option<&'a Benchmark> {
+    benchmarks
+        .iter()
+        .find(|b| b.joiner == joiner && b.params == *params && !b.times_ns.is_empty())
+}
+
+/// Compares the current results of `group` against its baseline. Checks the time of the latency
+/// hiding joiner and the overhead of every joiner against the unpatched rayon, wherever both runs
+/// have the benchmarks. A change is a regression if it is significantly more than `tolerance`,
+/// e.g. 0.05 for 5% slower.
+pub fn compare(
+    group: &str,
+    baseline: &[Benchmark],
+    current: &[Benchmark],
+    tolerance: f64,
+    bootstrap: &mut Bootstrap,
+) -> Vec<Comparison> {
+    let mut comparisons = Vec::new();
+
+    for b in current.iter().filter(|b| !b.times_ns.is_empty()) {
+        let mut push = |check: Check, change: Estimate| {
+            comparisons.push(Comparison {
+                group: group.to_string(),
+                joiner: b.joiner.clone(),
+                params: b.params.clone(),
+                check,
+                change,
+                regression: change.low > 1.0 + tolerance,
+            })
+        };
+
+        if b.joiner == LATENCY_HIDING {
+            if let Some(base) = find(baseline, &b.joiner, &b.params) {
+                let change = bootstrap.estimate(&base.times_ns, &b.times_ns, |base, b| b / base);
+                push(Check::Time, change);
+            }
+        }
+
+        if b.joiner != SERIAL && b.joiner != OLD_RAYON {
+            let runs = (
+                find(baseline, &b.joiner, &b.params),
+                find(baseline, OLD_RAYON, &b.params),
+                find(current, OLD_RAYON, &b.params),
+            );
+
+            if let (Some(base), Some(base_old), Some(old)) = runs {
+                let change = bootstrap.estimate_all(
+                    [
+                        &base.times_ns,
+                        &base_old.times_ns,
+                        &b.times_ns,
+                        &old.times_ns,
+                    ],
+                    |[base, base_old, b, old]| (b / old) / (base / base_old),
+                );
+                push(Check::OverheadVsOldRayon, change);
+            }
+        }
+    }
+
+    comparisons
+}
+
+/// Prints comparisons as a table.
+pub struct ComparisonTable<'a>(pub &'a [Comparison]);
+
+impl std::fmt::Display for ComparisonTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<20} {:<16} {:<22} {:<28} {:<10} params",
+            "group", "joiner", "check", "change", "regression"
+        )?;
+
+        for comparison in self.0 {
+            writeln!(
+                f,
+                "{:<20} {:<16} {:<22} {:<28} {:<10} {}",
+                comparison.group,
+                comparison.joiner,
+                comparison.check.to_string(),
+                comparison.change.to_string(),
+                if comparison.regression { "yes" } else { "no" },
+                comparison.params
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::CLASSIC;
+
+    const PARAMS: &str = "Work ms: 10 | Latency p: 0.5 | Cores: 4";
+
+    /// Samples scattered by up to 2% around `time_ms`.
+    fn benchmark(joiner: &str, time_ms: f64) -> Benchmark {
+        Benchmark {
+            joiner: joiner.to_string(),
+            params: Params::parse(PARAMS),
+            times_ns: (0..20)
+                .map(|i| time_ms * 1e6 * (0.98 + 0.04 * (i % 5) as f64 / 4.0))
+                .collect(),
+            utilization: None,
+        }
+    }
+
+    fn compare_runs(baseline: &[Benchmark], current: &[Benchmark]) -> Vec<Comparison> {
+        compare(
+            "Group",
+            baseline,
+            current,
+            0.05,
+            &mut Bootstrap::new(1000, 0.95, 0),
+        )
+    }
+
+    fn check<'a>(comparisons: &'a [Comparison], joiner: &str, check: Check) -> &'a Comparison {
+        comparisons
+            .iter()
+            .find(|c| c.joiner == joiner && c.check == check)
+            .unwrap()
+    }
+
+    #[test]
+    fn flags_regression() {
+        let baseline = [
+            benchmark(LATENCY_HIDING, 100.0),
+            benchmark(CLASSIC, 200.0),
+            benchmark(OLD_RAYON, 200.0),
+        ];
+        let current = [
+            benchmark(LATENCY_HIDING, 130.0),
+            benchmark(CLASSIC, 200.0),
+            benchmark(OLD_RAYON, 200.0),
+        ];
+
+        let comparisons = compare_runs(&baseline, &current);
+
+        let time = check(&comparisons, LATENCY_HIDING, Check::Time);
+        assert!(time.regression);
+        assert!((time.change.point - 1.3).abs() < 1e-9);
+        assert!(check(&comparisons, LATENCY_HIDING, Check::OverheadVsOldRayon).regression);
+        assert!(!check(&comparisons, CLASSIC, Check::OverheadVsOldRayon).regression);
+    }
+
+    #[test]
+    fn tolerates_no_change() {
+        let run = [
+            benchmark(LATENCY_HIDING, 100.0),
+            benchmark(CLASSIC, 200.0),
+            benchmark(OLD_RAYON, 200.0),
+        ];
+
+        let comparisons = compare_runs(&run, &run);
+
+        assert_eq!(comparisons.len(), 3);
+        for comparison in &comparisons {
+            assert!(!comparison.regression);
+            assert!(comparison.change.low <= 1.0 && 1.0 <= comparison.change.high);
+        }
+    }
+
+    #[test]
+    fn tolerates_old_rayon_getting_slower() {
+        // Only the overhead against the unpatched rayon counts, not its own time
+        let baseline = [benchmark(CLASSIC, 200.0), benchmark(OLD_RAYON, 200.0)];
+        let current = [benchmark(CLASSIC, 300.0), benchmark(OLD_RAYON, 300.0)];
+
+        let comparisons = compare_runs(&baseline, &current);
+
+        assert_eq!(comparisons.len(), 1);
+        assert!(!comparisons[0].regression);
+    }
+
+    #[test]
+    fn skips_overhead_without_old_rayon_runs() {
+        let baseline = [benchmark(LATENCY_HIDING, 100.0), benchmark(CLASSIC, 200.0)];
+        let current = [
+            benchmark(LATENCY_HIDING, 100.0),
+            benchmark(CLASSIC, 400.0),
+            benchmark(OLD_RAYON, 200.0),
+        ];
+
+        let comparisons = compare_runs(&baseline, &current);
+
+        assert_eq!(comparisons.len(), 1);
+        assert_eq!(comparisons[0].joiner, LATENCY_HIDING);
+        assert!(comparisons[0].check == Check::Time);
+    }
+}
@@ -0,0 +1,62 @@
+use benchmarks::analysis::{self, parse_confidence, Bootstrap, Reference, ScalingTable};
+use benchmarks::metadata::{self, RunMetadata, METADATA_FILE};
+use benchmarks::model::{self, PredictionTable};
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Speedup, efficiency and Karp–Flatt serial fraction with bootstrap confidence intervals, and the
+/// time the model predicts against the measured time, from the results criterion saved for a
+/// benchmark group
+#[derive(Parser)]
+struct Args {
+    /// Criterion benchmark group, e.g. "MapReduce Fib"
+    group: String,
+    /// Directory of the group's results, defaults to where criterion writes them
+    #[clap(long)]
+    dir: Option<PathBuf>,
+    /// Criterion runs to pool samples over: "new" for the latest run, or names passed to
+    /// `--save-baseline`. Can be given multiple times.
+    #[clap(long, default_value = "new")]
+    run: Vec<String>,
+    #[clap(long, arg_enum, default_value = "serial")]
+    reference: Reference,
+    #[clap(long, default_value = "10000")]
+    resamples: usize,
+    #[clap(long, default_value = "0.95", parse(try_from_str = parse_confidence))]
+    confidence: f64,
+    #[clap(long, default_value = "0")]
+    seed: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let dir = args
+        .dir
+        .unwrap_or_else(|| metadata::criterion_group_dir(&args.group));
+
+    let metadata = RunMetadata::read_json(dir.join(METADATA_FILE));
+    match &metadata {
+        Ok(metadata) => print!("{}", metadata),
+        Err(e) => eprintln!("No run metadata for {}: {}", args.group, e),
+    }
+
+    let benchmarks = analysis::load_criterion_group(&dir, &args.run)
+        .unwrap_or_else(|e| panic!("Could not load results from {}: {}", dir.display(), e));
+
+    let mut bootstrap = Bootstrap::new(args.resamples, args.confidence, args.seed);
+    let rows = analysis::scaling(&benchmarks, args.reference, &mut bootstrap);
+
+    print!("{}", ScalingTable(&rows));
+
+    // Benches without a core count parameter use all cores the process may run on
+    let workers = metadata
+        .ok()
+        .map(|metadata| metadata.allowed_cpus.unwrap_or(metadata.online_cpus));
+    match model::predictions(&benchmarks, workers, &mut bootstrap) {
+        Ok(predictions) if predictions.is_empty() => {
+            eprintln!("The model doesn't know the workload of {}", args.group)
+        }
+        Ok(predictions) => print!("{}", PredictionTable(&predictions)),
+        Err(e) => eprintln!("No predictions for {}: {}", args.group, e),
+    }
+}
@@ -0,0 +1,185 @@
+use benchmarks::analysis::{self, parse_confidence, Bootstrap};
+use benchmarks::baseline::{self, Baseline, ComparisonTable, GroupBaseline};
+use benchmarks::metadata::{self, RunMetadata, METADATA_FILE};
+use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Groups sweeping over workloads, joiners, core counts and latencies, run by the `sweep` script
+const DEFAULT_GROUPS: [&str; 3] = ["MapReduce Fib", "Old vs New Rayon", "Fib Parameter Sweep"];
+
+/// Baseline and the results it is saved from or compared with
+#[derive(clap::Args)]
+struct Results {
+    /// Name of the baseline
+    name: String,
+    /// Baseline file, defaults to <name>.json in the baselines directory
+    #[clap(long)]
+    file: Option<PathBuf>,
+    /// Criterion benchmark groups, defaults to the groups sweeping over cores and latency. Can be
+    /// given multiple times.
+    #[clap(short, long)]
+    group: Vec<String>,
+    /// Criterion runs to pool samples over: "new" for the latest run, or names passed to
+    /// `--save-baseline`. Can be given multiple times.
+    #[clap(long, default_value = "new")]
+    run: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Saves the latest criterion results as a named baseline
+    Save(Results),
+    /// Compares the latest criterion results against a named baseline, exiting with a non-zero
+    /// status if the latency hiding joiner got slower or any joiner's overhead over the unpatched
+    /// rayon increased, or if there was nothing to compare
+    Compare {
+        #[clap(flatten)]
+        results: Results,
+        /// Relative slowdown tolerated, e.g. 0.05 for 5%
+        #[clap(long, default_value = "0.05")]
+        tolerance: f64,
+        #[clap(long, default_value = "10000")]
+        resamples: usize,
+        #[clap(long, default_value = "0.95", parse(try_from_str = parse_confidence))]
+        confidence: f64,
+        #[clap(long, default_value = "0")]
+        seed: u64,
+    },
+}
+
+/// Saves benchmark results as a named baseline and checks later runs against it for regressions,
+/// e.g. before upgrading the rayon fork
+#[derive(Parser)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+fn main() {
+    let args = Args::parse();
+    let (Command::Save(results) | Command::Compare { results, .. }) = &args.command;
+
+    let path = results
+        .file
+        .clone()
+        .unwrap_or_else(|| baseline::baseline_path(&results.name));
+    let groups = if results.group.is_empty() {
+        DEFAULT_GROUPS
+            .iter()
+            .map(|group| group.to_string())
+            .collect()
+    } else {
+        results.group.clone()
+    };
+
+    let load = |group: &str| {
+        let dir = metadata::criterion_group_dir(group);
+        analysis::load_criterion_group(&dir, &results.run).map_err(|e| {
+            format!(
+                "Could not load results of {} from {}: {}",
+                group,
+                dir.display(),
+                e
+            )
+        })
+    };
+
+    match args.command {
+        Command::Save(_) => {
+            let groups: BTreeMap<String, GroupBaseline> = groups
+                .iter()
+                .map(|group| {
+                    let dir = metadata::criterion_group_dir(group);
+                    let baseline = GroupBaseline {
+                        metadata: RunMetadata::read_json(dir.join(METADATA_FILE)).ok(),
+                        benchmarks: load(group)?,
+                    };
+
+                    Ok((group.clone(), baseline))
+                })
+                .collect::<Result<_, String>>()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1);
+                });
+
+            let baseline = Baseline {
+                name: results.name.clone(),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                groups,
+            };
+
+            baseline
+                .write_json(&path)
+                .unwrap_or_else(|e| panic!("Could not write {}: {}", path.display(), e));
+            println!("{}", path.display());
+        }
+        Command::Compare {
+            tolerance,
+            resamples,
+            confidence,
+            seed,
+            ..
+        } => {
+            let baseline = Baseline::read_json(&path)
+                .unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e));
+            let mut bootstrap = Bootstrap::new(resamples, confidence, seed);
+            let mut comparisons = Vec::new();
+
+            for group in &groups {
+                let saved = match baseline.groups.get(group) {
+                    Some(saved) => saved,
+                    None => {
+                        eprintln!("Baseline {} has no results for {}", results.name, group);
+                        continue;
+                    }
+                };
+
+                if let Some(metadata) = &saved.metadata {
+                    println!("{} baseline:", group);
+                    print!("{}", metadata);
+                }
+
+                let current = match load(group) {
+                    Ok(current) => current,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        continue;
+                    }
+                };
+
+                comparisons.extend(baseline::compare(
+                    group,
+                    &saved.benchmarks,
+                    &current,
+                    tolerance,
+                    &mut bootstrap,
+                ));
+            }
+
+            if comparisons.is_empty() {
+                eprintln!("Nothing to compare against {}", results.name);
+                exit(1);
+            }
+
+            print!("{}", ComparisonTable(&comparisons));
+
+            let regressions = comparisons.iter().filter(|c| c.regression).count();
+            if regressions > 0 {
+                eprintln!(
+                    "{} of {} comparisons regressed against {}",
+                    regressions,
+                    comparisons.len(),
+                    results.name
+                );
+                exit(1);
+            }
+        }
+    }
+}
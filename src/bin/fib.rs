@@ -1,9 +1,17 @@
+use benchmarks::affinity::{self, Placement};
 use benchmarks::fib::{fib, fib_single_future};
+use benchmarks::kv_service::KvServer;
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::service_model::ServiceModel;
+use benchmarks::thread_pool::ThreadPoolConfig;
 use benchmarks::{
-    build_global_threadpool, parse_latency_p, ExecutionMode, Parallel, ParallelLH, Serial, Work,
+    histograms, parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Parallel,
+    ParallelLH, Serial, StackSize, Work,
 };
 use clap::Parser;
 use pin_utils::pin_mut;
+use std::process::exit;
 
 #[derive(Parser)]
 struct Args {
@@ -19,19 +27,94 @@ struct Args {
     latency_p: Option<f32>,
     #[clap(long, default_value = "25")]
     serial_cutoff: u32,
+    /// Instead of simulated latency, send a request to a local KV server taking this long to
+    /// service each request (latency ms and latency p are ignored)
+    #[clap(long)]
+    kv_service_ms: Option<u64>,
+    /// Number of requests the local KV server services concurrently
+    #[clap(long, default_value = "64")]
+    kv_concurrency: usize,
+    /// Instead of simulated latency, send a request to a simulated service taking this long to
+    /// service each request, with latency growing with the requests queued (latency ms and latency
+    /// p are ignored)
+    #[clap(long)]
+    model_service_ms: Option<u64>,
+    /// Number of requests the simulated service services concurrently
+    #[clap(long, default_value = "16")]
+    model_servers: usize,
+    /// Number of requests that can queue up at the simulated service before it rejects requests
+    #[clap(long, default_value = "64")]
+    model_queue_capacity: usize,
+    /// Record latency histograms and print them at the end of the run
+    #[clap(long)]
+    histograms: bool,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
     /// Defaults to number of cores on machine
     #[clap(short, long)]
     cores: Option<usize>,
-    /// In multiples of MB. Defaults to Rust stack size default, which is 2MB.
-    #[clap(short, long)]
-    stack_size: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(short, long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
 }
 
 fn main() {
     let args = Args::parse();
-    let work = Work::new(args.latency_ms, args.latency_p);
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let model = args.model_service_ms.map(|service_ms| {
+        let model = ServiceModel::new(args.model_servers, args.model_queue_capacity, service_ms);
+        &*Box::leak(Box::new(model))
+    });
 
-    build_global_threadpool(args.cores, args.stack_size);
+    let work = if let Some(service_ms) = args.kv_service_ms {
+        let server = KvServer::start(service_ms, args.kv_concurrency).unwrap();
+        Work::RemoteRequest {
+            addr: server.addr(),
+        }
+    } else if let Some(model) = model {
+        Work::QueuedService { model }
+    } else {
+        Work::new(args.latency_ms, args.latency_p)
+    };
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(args.n)
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    if args.histograms {
+        histograms::enable();
+    }
+
+    let measurement = Measurement::start();
 
     let (fib, calls) = if args.single_future_mode {
         let mut r: Option<(u32, u32)> = None;
@@ -53,5 +136,27 @@ fn main() {
         }
     };
 
+    let report = measurement.finish();
+    histograms::record_run(report.wall_clock);
+
     println!("result: {} calls: {}", fib, calls);
+    println!("{}", report);
+
+    if let Some(model) = model {
+        println!("{}", model.stats());
+    }
+
+    if args.histograms {
+        print!("{}", histograms::snapshot());
+    }
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
 }
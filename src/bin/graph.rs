@@ -0,0 +1,151 @@
+use benchmarks::affinity::{self, Placement};
+use benchmarks::graph::{
+    bfs, dfs, parse_rmat_scale, parse_vertices, Graph, GraphType, Traversal, TraversalStats,
+};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Joiner, Parallel, ParallelLH,
+    Serial, StackSize, Work,
+};
+use clap::Parser;
+use std::process::exit;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(short, long, arg_enum)]
+    mode: ExecutionMode,
+    /// DFS recursion depth grows with the traversal tree depth, use a large --stack-size
+    #[clap(short, long, arg_enum, default_value = "bfs")]
+    traversal: Traversal,
+    #[clap(short, long, arg_enum, default_value = "erdos-renyi")]
+    graph: GraphType,
+    #[clap(long, default_value = "0")]
+    seed: u64,
+    #[clap(long, default_value = "0")]
+    source: u32,
+    /// Erdős–Rényi graphs only
+    #[clap(short = 'n', long, default_value = "100000", parse(try_from_str = parse_vertices))]
+    vertices: usize,
+    /// Erdős–Rényi graphs only
+    #[clap(short, long, default_value = "8.0")]
+    average_degree: f64,
+    /// R-MAT graphs only, the graph has 2^scale vertices
+    #[clap(long, default_value = "16", parse(try_from_str = parse_rmat_scale))]
+    scale: u32,
+    /// R-MAT graphs only
+    #[clap(short, long, default_value = "8")]
+    edge_factor: usize,
+    #[clap(short, long)]
+    latency_ms: Option<u64>,
+    #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
+    latency_p: Option<f32>,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
+    /// Defaults to number of cores on machine
+    #[clap(short, long)]
+    cores: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(short, long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
+}
+
+fn run<J: Joiner>(args: &Args, graph: &Graph, work: &Work) -> TraversalStats {
+    match args.traversal {
+        Traversal::Bfs => bfs::<J>(graph, args.source, work),
+        Traversal::Dfs => dfs::<J>(graph, args.source, work),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let work = Work::new(args.latency_ms, args.latency_p);
+
+    let graph = match args.graph {
+        GraphType::ErdosRenyi => Graph::erdos_renyi(args.vertices, args.average_degree, args.seed),
+        GraphType::Rmat => Graph::rmat(args.scale, args.edge_factor, args.seed),
+    };
+    println!(
+        "vertices: {} edges: {}",
+        graph.num_vertices(),
+        graph.num_edges()
+    );
+
+    if args.source as usize >= graph.num_vertices() {
+        eprintln!(
+            "Source vertex {} is out of range, the graph has {} vertices",
+            args.source,
+            graph.num_vertices()
+        );
+        exit(1);
+    }
+
+    // BFS only recurses as deep as the joins over a frontier. The depth of the DFS traversal tree
+    // can't be known before traversing, so DFS gets the worst case of a path through every vertex.
+    // That is a cap rather than an estimate, auto stacks clamp it and usually end up far too large
+    let depth = match args.traversal {
+        Traversal::Bfs => usize::BITS - graph.num_vertices().leading_zeros(),
+        Traversal::Dfs => u32::try_from(graph.num_vertices()).unwrap_or(u32::MAX),
+    };
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(depth)
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    let measurement = Measurement::start();
+
+    let stats = match args.mode {
+        ExecutionMode::LatencyHiding => run::<ParallelLH>(&args, &graph, &work),
+        ExecutionMode::Parallel => run::<Parallel>(&args, &graph, &work),
+        ExecutionMode::Serial => run::<Serial>(&args, &graph, &work),
+    };
+    let report = measurement.finish();
+
+    println!(
+        "visited: {} edges examined: {} depth: {}",
+        stats.vertices_visited, stats.edges_examined, stats.depth
+    );
+
+    println!("{}", report);
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
+}
@@ -1,8 +1,16 @@
-use benchmarks::map_reduce::{map_reduce, map_reduce_fib};
+use benchmarks::affinity::{self, Placement};
+use benchmarks::map_reduce::{
+    map_reduce_async, map_reduce_fib, map_reduce_with_grain, parse_grain, Grain,
+};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::thread_pool::ThreadPoolConfig;
 use benchmarks::{
-    build_global_threadpool, parse_latency_p, ExecutionMode, Parallel, ParallelLH, Serial, Work,
+    histograms, parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Joiner, Parallel,
+    ParallelLH, Serial, StackSize, Work,
 };
 use clap::Parser;
+use std::process::exit;
 
 #[derive(Parser)]
 struct Args {
@@ -10,6 +18,13 @@ struct Args {
     mode: ExecutionMode,
     #[clap(long, default_value = "10")]
     map_n: usize,
+    /// Number of items processed sequentially at the leaves, or "adaptive"
+    #[clap(short, long, default_value = "1", parse(try_from_str = parse_grain))]
+    grain: Grain,
+    /// Map with an async function that awaits the latency on every item, so it can't be combined
+    /// with latency p
+    #[clap(long, conflicts_with = "latency-p")]
+    async_map: bool,
     #[clap(short, long)]
     latency_ms: Option<u64>,
     #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
@@ -18,55 +33,113 @@ struct Args {
     fib_n: u32,
     #[clap(short, long, default_value = "25")]
     serial_cutoff: u32,
+    /// Record latency histograms and print them at the end of the run
+    #[clap(long)]
+    histograms: bool,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
     /// Defaults to number of cores on machine
     #[clap(short, long)]
     cores: Option<usize>,
-    /// In multiples of MB. Defaults to Rust stack size default, which is 2MB.
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
     #[clap(long)]
-    stack_size: Option<usize>,
+    stack_usage: bool,
+}
+
+fn run<J: Joiner>(args: &Args, work: &Work, items: &mut [u32]) -> u32 {
+    if args.async_map {
+        let map =
+            |n: &mut u32| map_reduce_fib::async_map::<J>(*n, args.latency_ms, args.serial_cutoff);
+
+        map_reduce_async::<J, _, _, _, _, _, _>(
+            items,
+            args.grain,
+            &map,
+            &map_reduce_fib::reduce,
+            &map_reduce_fib::identity,
+        )
+    } else {
+        let map = |n: &mut u32| map_reduce_fib::map::<J>(*n, work, args.serial_cutoff);
+
+        map_reduce_with_grain::<J, _, _, _, _, _>(
+            items,
+            args.grain,
+            &map,
+            &map_reduce_fib::reduce,
+            &map_reduce_fib::identity,
+        )
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
     let work = Work::new(args.latency_ms, args.latency_p);
 
     let mut i = vec![args.fib_n; args.map_n];
 
-    build_global_threadpool(args.cores, args.stack_size);
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(usize::BITS - args.map_n.leading_zeros() + args.fib_n)
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    if args.histograms {
+        histograms::enable();
+    }
+
+    let measurement = Measurement::start();
 
     let r = match args.mode {
-        ExecutionMode::LatencyHiding => {
-            let map =
-                |n: &mut u32| map_reduce_fib::map::<ParallelLH>(*n, &work, args.serial_cutoff);
-
-            map_reduce::<ParallelLH, _, _, _, _, _>(
-                &mut i,
-                &map,
-                &map_reduce_fib::reduce,
-                &map_reduce_fib::identity,
-            )
-        }
-        ExecutionMode::Parallel => {
-            let map = |n: &mut u32| map_reduce_fib::map::<Parallel>(*n, &work, args.serial_cutoff);
-
-            map_reduce::<Parallel, _, _, _, _, _>(
-                &mut i,
-                &map,
-                &map_reduce_fib::reduce,
-                &map_reduce_fib::identity,
-            )
-        }
-        ExecutionMode::Serial => {
-            let map = |n: &mut u32| map_reduce_fib::map::<Serial>(*n, &work, args.serial_cutoff);
-
-            map_reduce::<Serial, _, _, _, _, _>(
-                &mut i,
-                &map,
-                &map_reduce_fib::reduce,
-                &map_reduce_fib::identity,
-            )
-        }
+        ExecutionMode::LatencyHiding => run::<ParallelLH>(&args, &work, &mut i),
+        ExecutionMode::Parallel => run::<Parallel>(&args, &work, &mut i),
+        ExecutionMode::Serial => run::<Serial>(&args, &work, &mut i),
     };
 
+    let report = measurement.finish();
+    histograms::record_run(report.wall_clock);
+
     println!("Final value: {}", r);
+    println!("{}", report);
+
+    if args.histograms {
+        print!("{}", histograms::snapshot());
+    }
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
 }
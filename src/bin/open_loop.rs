@@ -0,0 +1,131 @@
+use benchmarks::affinity::{self, Placement};
+use benchmarks::map_reduce::{map_reduce, map_reduce_fib};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::open_loop::{parse_rate, run_open_loop, OpenLoopReport};
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    histograms, parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Joiner, Parallel,
+    ParallelLH, Serial, StackSize, Work,
+};
+use clap::Parser;
+use std::process::exit;
+
+#[derive(Parser)]
+struct Args {
+    /// Joiner used inside each request, requests themselves always run concurrently
+    #[clap(short, long, arg_enum)]
+    mode: ExecutionMode,
+    /// Mean request arrivals per second
+    #[clap(short, long, default_value = "100.0", parse(try_from_str = parse_rate))]
+    rate: f64,
+    #[clap(short = 'n', long, default_value = "1000")]
+    requests: usize,
+    #[clap(long, default_value = "0")]
+    seed: u64,
+    #[clap(long, default_value = "4")]
+    map_n: usize,
+    #[clap(short, long, default_value = "20")]
+    fib_n: u32,
+    #[clap(short, long, default_value = "15")]
+    serial_cutoff: u32,
+    #[clap(short, long)]
+    latency_ms: Option<u64>,
+    #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
+    latency_p: Option<f32>,
+    /// Record latency histograms and print them at the end of the run
+    #[clap(long)]
+    histograms: bool,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
+    /// Defaults to number of cores on machine
+    #[clap(short, long)]
+    cores: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
+}
+
+fn run<J: Joiner>(args: &Args, work: Work) -> OpenLoopReport {
+    let (map_n, fib_n, serial_cutoff) = (args.map_n, args.fib_n, args.serial_cutoff);
+
+    run_open_loop(args.rate, args.requests, args.seed, move || {
+        let mut items = vec![fib_n; map_n];
+        let map = |n: &mut u32| map_reduce_fib::map::<J>(*n, &work, serial_cutoff);
+
+        map_reduce::<J, _, _, _, _, _>(
+            &mut items,
+            &map,
+            &map_reduce_fib::reduce,
+            &map_reduce_fib::identity,
+        );
+    })
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let work = Work::new(args.latency_ms, args.latency_p);
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(usize::BITS - args.map_n.leading_zeros() + args.fib_n)
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    if args.histograms {
+        histograms::enable();
+    }
+
+    let measurement = Measurement::start();
+
+    let report = match args.mode {
+        ExecutionMode::LatencyHiding => run::<ParallelLH>(&args, work),
+        ExecutionMode::Parallel => run::<Parallel>(&args, work),
+        ExecutionMode::Serial => run::<Serial>(&args, work),
+    };
+
+    println!("{}", report);
+    println!("{}", measurement.finish());
+
+    if args.histograms {
+        print!("{}", histograms::snapshot());
+    }
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
+}
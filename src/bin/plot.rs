@@ -0,0 +1,127 @@
+use benchmarks::analysis::{self, parse_confidence, Bootstrap, Reference, LATENCY_PARAM};
+use benchmarks::metadata;
+use benchmarks::plot::{self, Chart, Format};
+use clap::{ArgEnum, Parser};
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, ArgEnum)]
+enum Kind {
+    /// Speedup over workers of every joiner, one plot per latency, with the speedup the model
+    /// predicts
+    Speedup,
+    /// Speedup over workers of every joiner without latency
+    Overhead,
+    /// Speedup of one joiner over two parameters, e.g. work ms × latency p of the parameter sweep
+    Heatmap,
+}
+
+impl Kind {
+    fn default_group(&self) -> &'static str {
+        match self {
+            Kind::Speedup => "MapReduce Fib",
+            Kind::Overhead => "Old vs New Rayon",
+            Kind::Heatmap => "Fib Parameter Sweep",
+        }
+    }
+}
+
+/// Renders plots from the results criterion saved for a benchmark group
+#[derive(Parser)]
+struct Args {
+    #[clap(arg_enum)]
+    kind: Kind,
+    /// Criterion benchmark group, defaults to the group the kind of plot was made for
+    #[clap(short, long)]
+    group: Option<String>,
+    /// Directory of the group's results, defaults to where criterion writes them
+    #[clap(long)]
+    dir: Option<PathBuf>,
+    /// Criterion runs to pool samples over: "new" for the latest run, or names passed to
+    /// `--save-baseline`. Can be given multiple times.
+    #[clap(long, default_value = "new")]
+    run: Vec<String>,
+    #[clap(short, long, default_value = "plotting/plots")]
+    output_dir: PathBuf,
+    #[clap(short, long, arg_enum, default_value = "png")]
+    format: Format,
+    #[clap(long, arg_enum, default_value = "serial")]
+    reference: Reference,
+    /// Joiner shown in the heatmap
+    #[clap(long, default_value = "Latency Hiding")]
+    joiner: String,
+    #[clap(long, default_value = "Work ms")]
+    x_param: String,
+    #[clap(long, default_value = "Latency p")]
+    y_param: String,
+    #[clap(long, default_value = "1000")]
+    resamples: usize,
+    #[clap(long, default_value = "0.95", parse(try_from_str = parse_confidence))]
+    confidence: f64,
+    #[clap(long, default_value = "0")]
+    seed: u64,
+}
+
+fn save(args: &Args, title: &str, chart: &impl Chart) {
+    let path = args.output_dir.join(plot::file_name(title, args.format));
+
+    chart
+        .save(&path, args.format)
+        .unwrap_or_else(|e| panic!("Could not save {}: {}", path.display(), e));
+    println!("{}", path.display());
+}
+
+fn main() {
+    let args = Args::parse();
+    let group = args
+        .group
+        .clone()
+        .unwrap_or_else(|| args.kind.default_group().to_string());
+    let dir = args
+        .dir
+        .clone()
+        .unwrap_or_else(|| metadata::criterion_group_dir(&group));
+
+    let benchmarks = analysis::load_criterion_group(&dir, &args.run)
+        .unwrap_or_else(|e| panic!("Could not load results from {}: {}", dir.display(), e));
+    let mut bootstrap = Bootstrap::new(args.resamples, args.confidence, args.seed);
+
+    std::fs::create_dir_all(&args.output_dir).expect("Could not create output directory");
+
+    match args.kind {
+        Kind::Speedup | Kind::Overhead => {
+            // Groups without a latency parameter get both kinds of plots
+            let rows: Vec<_> = analysis::scaling(&benchmarks, args.reference, &mut bootstrap)
+                .into_iter()
+                .filter(|row| {
+                    row.params
+                        .get(LATENCY_PARAM)
+                        .is_none_or(|latency| match args.kind {
+                            Kind::Overhead => latency == "0",
+                            _ => latency != "0",
+                        })
+                })
+                .collect();
+
+            for (params, mut chart) in plot::speedup_charts(&group, &rows) {
+                if let Kind::Speedup = args.kind {
+                    match plot::model_series(&benchmarks, &params) {
+                        Ok(series) => chart.series.extend(series),
+                        Err(e) => eprintln!("Plotting without the model: {}", e),
+                    }
+                }
+
+                save(&args, &chart.title, &chart);
+            }
+        }
+        Kind::Heatmap => {
+            let heatmap = plot::speedup_heatmap(
+                &group,
+                &benchmarks,
+                &args.joiner,
+                (&args.x_param, &args.y_param),
+            );
+
+            save(&args, &heatmap.title, &heatmap);
+        }
+    }
+}
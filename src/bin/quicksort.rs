@@ -1,8 +1,17 @@
-use benchmarks::quicksort::{generate_random_sequence, quicksort};
+use benchmarks::affinity::{self, Placement};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::quicksort::{
+    generate_random_elements, generate_random_remote_keys, quicksort, quicksort_remote_keys,
+    ElementType, LargeElement, RandomElement,
+};
+use benchmarks::thread_pool::ThreadPoolConfig;
 use benchmarks::{
-    build_global_threadpool, parse_latency_p, ExecutionMode, Parallel, ParallelLH, Serial, Work,
+    parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Parallel, ParallelLH, Serial,
+    StackSize, Work,
 };
 use clap::Parser;
+use std::process::exit;
 
 #[derive(Parser)]
 struct Args {
@@ -10,38 +19,135 @@ struct Args {
     mode: ExecutionMode,
     #[clap(short, long, default_value = "8000000")]
     n: usize,
+    #[clap(short, long, arg_enum, default_value = "i32")]
+    element: ElementType,
+    /// Incur latency when fetching comparison keys, instead of at the leaves of the computation
+    #[clap(long)]
+    remote_keys: bool,
     #[clap(short, long)]
     latency_ms: Option<u64>,
     #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
     latency_p: Option<f32>,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
     /// Defaults to number of cores on machine
     #[clap(short, long)]
     cores: Option<usize>,
-    /// In multiples of MB. Defaults to Rust stack size default, which is 2MB.
-    #[clap(short, long)]
-    stack_size: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(short, long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
 }
 
-fn main() {
-    let args = Args::parse();
-    let work = Work::new(args.latency_ms, args.latency_p);
-
-    let mut v = generate_random_sequence(args.n);
+fn run<T: RandomElement>(args: &Args, work: &Work) {
+    let mut v = generate_random_elements::<T>(args.n);
     println!("Unsorted: {:?}...{:?}", &v[..3], &v[v.len() - 3..]);
 
-    build_global_threadpool(args.cores, args.stack_size);
+    let measurement = Measurement::start();
 
     match args.mode {
         ExecutionMode::LatencyHiding => {
-            quicksort::<ParallelLH, _>(&mut v, &work);
+            quicksort::<ParallelLH, _>(&mut v, work);
         }
         ExecutionMode::Parallel => {
-            quicksort::<Parallel, _>(&mut v, &work);
+            quicksort::<Parallel, _>(&mut v, work);
         }
         ExecutionMode::Serial => {
-            quicksort::<Serial, _>(&mut v, &work);
+            quicksort::<Serial, _>(&mut v, work);
         }
     }
 
+    let report = measurement.finish();
+
     println!("Sorted: {:?}...{:?}", &v[..3], &v[v.len() - 3..]);
+    println!("{}", report);
+}
+
+fn run_remote_keys<T: RandomElement>(args: &Args, work: &Work) {
+    let mut v = generate_random_remote_keys::<T>(args.n);
+
+    let measurement = Measurement::start();
+
+    match args.mode {
+        ExecutionMode::LatencyHiding => {
+            quicksort_remote_keys::<ParallelLH, _>(&mut v, work);
+        }
+        ExecutionMode::Parallel => {
+            quicksort_remote_keys::<Parallel, _>(&mut v, work);
+        }
+        ExecutionMode::Serial => {
+            quicksort_remote_keys::<Serial, _>(&mut v, work);
+        }
+    }
+
+    let report = measurement.finish();
+
+    println!(
+        "Sorted: {:?}...{:?}",
+        v[..3].iter().map(|e| e.key()).collect::<Vec<_>>(),
+        v[v.len() - 3..].iter().map(|e| e.key()).collect::<Vec<_>>()
+    );
+    println!("{}", report);
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let work = Work::new(args.latency_ms, args.latency_p);
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    // Partitioning around the last element of random input keeps the recursion depth within a
+    // small multiple of log2(n)
+    let depth = 2 * (usize::BITS - args.n.leading_zeros());
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(depth)
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    match (args.element, args.remote_keys) {
+        (ElementType::I32, false) => run::<i32>(&args, &work),
+        (ElementType::String, false) => run::<String>(&args, &work),
+        (ElementType::Large, false) => run::<LargeElement>(&args, &work),
+        (ElementType::Boxed, false) => run::<Box<i32>>(&args, &work),
+        (ElementType::I32, true) => run_remote_keys::<i32>(&args, &work),
+        (ElementType::String, true) => run_remote_keys::<String>(&args, &work),
+        (ElementType::Large, true) => run_remote_keys::<LargeElement>(&args, &work),
+        (ElementType::Boxed, true) => run_remote_keys::<Box<i32>>(&args, &work),
+    }
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
 }
@@ -0,0 +1,60 @@
+use benchmarks::analysis::{self, parse_confidence, Bootstrap, Reference};
+use benchmarks::metadata::{self, RunMetadata, METADATA_FILE};
+use benchmarks::report::HtmlReport;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Static HTML report of a benchmark group with its parameters, environment, plots, speedup
+/// tables and worker utilization, from the results criterion saved for it
+#[derive(Parser)]
+struct Args {
+    /// Criterion benchmark group
+    #[clap(default_value = "MapReduce Fib")]
+    group: String,
+    /// Directory of the group's results, defaults to where criterion writes them
+    #[clap(long)]
+    dir: Option<PathBuf>,
+    /// Criterion runs to pool samples over: "new" for the latest run, or names passed to
+    /// `--save-baseline`. Can be given multiple times.
+    #[clap(long, default_value = "new")]
+    run: Vec<String>,
+    /// Defaults to experiment.html in the group's directory
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    #[clap(long, arg_enum, default_value = "serial")]
+    reference: Reference,
+    #[clap(long, default_value = "1000")]
+    resamples: usize,
+    #[clap(long, default_value = "0.95", parse(try_from_str = parse_confidence))]
+    confidence: f64,
+    #[clap(long, default_value = "0")]
+    seed: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    let dir = args
+        .dir
+        .unwrap_or_else(|| metadata::criterion_group_dir(&args.group));
+    let output = args.output.unwrap_or_else(|| dir.join("experiment.html"));
+
+    let metadata = RunMetadata::read_json(dir.join(METADATA_FILE))
+        .map_err(|e| eprintln!("No run metadata for {}: {}", args.group, e))
+        .ok();
+    let benchmarks = analysis::load_criterion_group(&dir, &args.run)
+        .unwrap_or_else(|e| panic!("Could not load results from {}: {}", dir.display(), e));
+
+    let mut bootstrap = Bootstrap::new(args.resamples, args.confidence, args.seed);
+    let report = HtmlReport::new(
+        &args.group,
+        metadata.as_ref(),
+        &benchmarks,
+        args.reference,
+        &mut bootstrap,
+    )
+    .unwrap_or_else(|e| panic!("Could not render plots: {}", e));
+
+    std::fs::write(&output, report.to_string())
+        .unwrap_or_else(|e| panic!("Could not write {}: {}", output.display(), e));
+    println!("{}", output.display());
+}
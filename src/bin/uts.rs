@@ -0,0 +1,133 @@
+use benchmarks::affinity::{self, Placement};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::uts::{parse_binomial_q, parse_branching_factor, uts, TreeShape, TreeType};
+use benchmarks::{
+    parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Parallel, ParallelLH, Serial,
+    StackSize, Work,
+};
+use clap::Parser;
+use std::process::exit;
+
+/// Defaults correspond to the T1 (geometric) and T3 (binomial) trees of the UTS benchmark suite.
+#[derive(Parser)]
+struct Args {
+    #[clap(short, long, arg_enum)]
+    mode: ExecutionMode,
+    #[clap(short, long, arg_enum, default_value = "geometric")]
+    tree: TreeType,
+    #[clap(long, default_value = "19")]
+    seed: u64,
+    /// Geometric trees only
+    #[clap(short, long, default_value = "4.0", parse(try_from_str = parse_branching_factor))]
+    branching_factor: f64,
+    /// Geometric trees only
+    #[clap(short = 'd', long, default_value = "10")]
+    max_depth: u32,
+    /// Binomial trees only
+    #[clap(short, long, default_value = "2000")]
+    root_children: u32,
+    /// Binomial trees only, q * m must be below 1 for the tree to be finite
+    #[clap(short, default_value = "0.124875", parse(try_from_str = parse_binomial_q))]
+    q: f64,
+    /// Binomial trees only, q * m must be below 1 for the tree to be finite
+    #[clap(short = 'n', default_value = "8")]
+    m: u32,
+    #[clap(short, long)]
+    latency_ms: Option<u64>,
+    #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
+    latency_p: Option<f32>,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
+    /// Defaults to number of cores on machine
+    #[clap(short, long)]
+    cores: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(short, long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    if matches!(args.tree, TreeType::Binomial) && args.q * args.m as f64 >= 1.0 {
+        eprintln!(
+            "q * m is {}, binomial trees only stay finite if it is below 1",
+            args.q * args.m as f64
+        );
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let work = Work::new(args.latency_ms, args.latency_p);
+
+    let shape = match args.tree {
+        TreeType::Binomial => TreeShape::Binomial {
+            root_children: args.root_children,
+            q: args.q,
+            m: args.m,
+        },
+        TreeType::Geometric => TreeShape::Geometric {
+            branching_factor: args.branching_factor,
+            max_depth: args.max_depth,
+        },
+    };
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(shape.depth_estimate())
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    let measurement = Measurement::start();
+
+    let stats = match args.mode {
+        ExecutionMode::LatencyHiding => uts::<ParallelLH>(&shape, args.seed, &work),
+        ExecutionMode::Parallel => uts::<Parallel>(&shape, args.seed, &work),
+        ExecutionMode::Serial => uts::<Serial>(&shape, args.seed, &work),
+    };
+    let report = measurement.finish();
+
+    println!(
+        "nodes: {} leaves: {} max depth: {}",
+        stats.nodes, stats.leaves, stats.max_depth
+    );
+
+    println!("{}", report);
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
+}
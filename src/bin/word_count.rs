@@ -0,0 +1,130 @@
+use benchmarks::affinity::{self, Placement};
+use benchmarks::map_reduce::map_reduce_word_count::{self, parse_vocabulary_size, WordCounts};
+use benchmarks::map_reduce::{map_reduce_with_grain, parse_grain, Grain};
+use benchmarks::measure::Measurement;
+use benchmarks::metadata::RunMetadata;
+use benchmarks::thread_pool::ThreadPoolConfig;
+use benchmarks::{
+    parse_latency_p, parse_stack_size, perf, stack, ExecutionMode, Joiner, Parallel, ParallelLH,
+    Serial, StackSize, Work,
+};
+use clap::Parser;
+use std::process::exit;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(short, long, arg_enum)]
+    mode: ExecutionMode,
+    #[clap(short, long, default_value = "10000")]
+    documents: usize,
+    #[clap(short, long, default_value = "1000")]
+    words_per_document: usize,
+    #[clap(short, long, default_value = "50000", parse(try_from_str = parse_vocabulary_size))]
+    vocabulary_size: usize,
+    /// Seed for generating the corpus
+    #[clap(long, default_value = "0")]
+    seed: u64,
+    /// Number of documents processed sequentially at the leaves, or "adaptive"
+    #[clap(short, long, default_value = "1", parse(try_from_str = parse_grain))]
+    grain: Grain,
+    #[clap(short, long)]
+    latency_ms: Option<u64>,
+    #[clap(short = 'p', long, parse(try_from_str = parse_latency_p))]
+    latency_p: Option<f32>,
+    /// Collect perf counters (context switches, migrations, cycles, ...) for the measured run
+    #[clap(long)]
+    perf_counters: bool,
+    /// Pin workers to CPUs, filling up cores and packages first (compact) or spreading out
+    /// (scatter)
+    #[clap(long, arg_enum)]
+    pin: Option<Placement>,
+    /// Defaults to number of cores on machine
+    #[clap(short, long)]
+    cores: Option<usize>,
+    /// In multiples of MB, or "auto" to size stacks based on workload depth. Defaults to Rust
+    /// stack size default, which is 2MB.
+    #[clap(short, long, parse(try_from_str = parse_stack_size))]
+    stack_size: Option<StackSize>,
+    /// Measure the stack high-water mark of every worker and print it at the end of the run. Not
+    /// available in serial mode, which runs on the unpainted main thread
+    #[clap(long)]
+    stack_usage: bool,
+}
+
+fn run<J: Joiner>(args: &Args, work: &Work, corpus: &mut [String]) -> WordCounts {
+    let map = |document: &mut String| map_reduce_word_count::map::<J>(document, work);
+
+    map_reduce_with_grain::<J, _, _, _, _, _>(
+        corpus,
+        args.grain,
+        &map,
+        &map_reduce_word_count::reduce,
+        &map_reduce_word_count::identity,
+    )
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.stack_usage && matches!(args.mode, ExecutionMode::Serial) {
+        eprintln!("--stack-usage measures worker threads, but serial mode runs on the main thread");
+        exit(1);
+    }
+    print!("{}", RunMetadata::collect());
+    let work = Work::new(args.latency_ms, args.latency_p);
+
+    let mut corpus = map_reduce_word_count::generate_corpus(
+        args.seed,
+        args.documents,
+        args.words_per_document,
+        args.vocabulary_size,
+    );
+
+    // Before building the threadpool, so workers inherit the counters
+    if args.perf_counters {
+        if let Err(e) = perf::open_counters() {
+            eprintln!("Could not open perf counters, running without them: {}", e);
+        }
+    }
+
+    if args.stack_usage {
+        stack::enable_tracking();
+    }
+
+    ThreadPoolConfig::new()
+        .threads(args.cores)
+        .stack_size(args.stack_size)
+        .workload_depth(usize::BITS - args.documents.leading_zeros())
+        .placement(args.pin)
+        .build_global()
+        .expect("Could not build threadpool");
+
+    let measurement = Measurement::start();
+
+    let counts = match args.mode {
+        ExecutionMode::LatencyHiding => run::<ParallelLH>(&args, &work, &mut corpus),
+        ExecutionMode::Parallel => run::<Parallel>(&args, &work, &mut corpus),
+        ExecutionMode::Serial => run::<Serial>(&args, &work, &mut corpus),
+    };
+    let report = measurement.finish();
+
+    let mut most_common: Vec<_> = counts.iter().collect();
+    most_common.sort_unstable_by(|a, b| b.1.cmp(a.1));
+
+    println!("Distinct words: {}", counts.len());
+    println!(
+        "Most common: {:?}",
+        &most_common[..most_common.len().min(5)]
+    );
+
+    println!("{}", report);
+
+    for (worker, cpu) in affinity::worker_cpus() {
+        println!("worker {}: pinned to cpu {}", worker, cpu);
+    }
+
+    if args.stack_usage {
+        for usage in stack::high_water_marks() {
+            println!("{}", usage);
+        }
+    }
+}
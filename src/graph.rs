@@ -0,0 +1,304 @@
+use crate::map_reduce::map_reduce;
+use crate::{Joiner, Work};
+use clap::ArgEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// R-MAT quadrant probabilities (d = 1 - a - b - c), as used by Graph500
+const RMAT_A: f64 = 0.57;
+const RMAT_B: f64 = 0.19;
+const RMAT_C: f64 = 0.19;
+/// Largest R-MAT scale whose vertices all get a `u32` id
+pub const MAX_RMAT_SCALE: u32 = 32;
+/// Most vertices a graph can have, vertex ids and the count itself are `u32`
+pub const MAX_VERTICES: usize = u32::MAX as usize;
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum GraphType {
+    ErdosRenyi,
+    Rmat,
+}
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum Traversal {
+    Bfs,
+    Dfs,
+}
+
+#[derive(Debug)]
+pub enum ParseRmatScaleError {
+    TooLarge,
+    ParseError,
+}
+
+impl std::error::Error for ParseRmatScaleError {}
+
+impl std::fmt::Display for ParseRmatScaleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseRmatScaleError::TooLarge => {
+                write!(f, "R-MAT scale must be at most {}", MAX_RMAT_SCALE)
+            }
+            ParseRmatScaleError::ParseError => {
+                write!(f, "Argument for R-MAT scale could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_rmat_scale(s: &str) -> Result<u32, ParseRmatScaleError> {
+    match s.parse::<u32>() {
+        Ok(scale) if scale > MAX_RMAT_SCALE => Err(ParseRmatScaleError::TooLarge),
+        Ok(scale) => Ok(scale),
+        Err(_) => Err(ParseRmatScaleError::ParseError),
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseVerticesError {
+    Zero,
+    TooLarge,
+    ParseError,
+}
+
+impl std::error::Error for ParseVerticesError {}
+
+impl std::fmt::Display for ParseVerticesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseVerticesError::Zero => write!(f, "Graph must have at least one vertex"),
+            ParseVerticesError::TooLarge => {
+                write!(f, "Graph can have at most {} vertices", MAX_VERTICES)
+            }
+            ParseVerticesError::ParseError => {
+                write!(f, "Argument for vertices could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_vertices(s: &str) -> Result<usize, ParseVerticesError> {
+    match s.parse::<usize>() {
+        Ok(0) => Err(ParseVerticesError::Zero),
+        Ok(vertices) if vertices > MAX_VERTICES => Err(ParseVerticesError::TooLarge),
+        Ok(vertices) => Ok(vertices),
+        Err(_) => Err(ParseVerticesError::ParseError),
+    }
+}
+
+/// Undirected graph in compressed sparse row format.
+pub struct Graph {
+    offsets: Vec<usize>,
+    edges: Vec<u32>,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct TraversalStats {
+    pub vertices_visited: u64,
+    pub edges_examined: u64,
+    pub depth: u32,
+}
+
+impl TraversalStats {
+    fn combine(s1: TraversalStats, s2: TraversalStats) -> TraversalStats {
+        TraversalStats {
+            vertices_visited: s1.vertices_visited + s2.vertices_visited,
+            edges_examined: s1.edges_examined + s2.edges_examined,
+            depth: s1.depth.max(s2.depth),
+        }
+    }
+}
+
+impl Graph {
+    fn from_edges(num_vertices: usize, edge_list: &[(u32, u32)]) -> Self {
+        let mut degrees = vec![0; num_vertices];
+
+        for &(u, v) in edge_list.iter().filter(|(u, v)| u != v) {
+            degrees[u as usize] += 1;
+            degrees[v as usize] += 1;
+        }
+
+        let mut offsets = Vec::with_capacity(num_vertices + 1);
+        offsets.push(0);
+        for degree in degrees {
+            offsets.push(offsets.last().unwrap() + degree);
+        }
+
+        let mut edges = vec![0; *offsets.last().unwrap()];
+        let mut next = offsets.clone();
+
+        for &(u, v) in edge_list.iter().filter(|(u, v)| u != v) {
+            edges[next[u as usize]] = v;
+            next[u as usize] += 1;
+            edges[next[v as usize]] = u;
+            next[v as usize] += 1;
+        }
+
+        Graph { offsets, edges }
+    }
+
+    /// G(n, m) random graph with `num_vertices * average_degree / 2` edges chosen uniformly at
+    /// random. Self loops are dropped, parallel edges are kept.
+    pub fn erdos_renyi(num_vertices: usize, average_degree: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let num_edges = (num_vertices as f64 * average_degree / 2.0) as usize;
+
+        let edge_list: Vec<(u32, u32)> = (0..num_edges)
+            .map(|_| {
+                (
+                    rng.gen_range(0..num_vertices as u32),
+                    rng.gen_range(0..num_vertices as u32),
+                )
+            })
+            .collect();
+
+        Self::from_edges(num_vertices, &edge_list)
+    }
+
+    /// R-MAT graph with `2^scale` vertices and `edge_factor * 2^scale` edges. Produces a power law
+    /// degree distribution, i.e. a few vertices with very wide fan-out.
+    pub fn rmat(scale: u32, edge_factor: usize, seed: u64) -> Self {
+        assert!(
+            scale <= MAX_RMAT_SCALE,
+            "R-MAT scale must be at most {}",
+            MAX_RMAT_SCALE
+        );
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let num_vertices = 1usize << scale;
+
+        let edge_list: Vec<(u32, u32)> = (0..edge_factor * num_vertices)
+            .map(|_| {
+                let (mut u, mut v) = (0u32, 0u32);
+
+                for bit in (0..scale).rev() {
+                    let r: f64 = rng.gen();
+
+                    if r < RMAT_A {
+                        // top left quadrant, neither bit set
+                    } else if r < RMAT_A + RMAT_B {
+                        v |= 1 << bit;
+                    } else if r < RMAT_A + RMAT_B + RMAT_C {
+                        u |= 1 << bit;
+                    } else {
+                        u |= 1 << bit;
+                        v |= 1 << bit;
+                    }
+                }
+
+                (u, v)
+            })
+            .collect();
+
+        Self::from_edges(num_vertices, &edge_list)
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.edges.len() / 2
+    }
+
+    /// Pretends the adjacency list lives on a remote machine, so fetching it may incur latency.
+    fn fetch_adjacency<J: Joiner>(&self, vertex: u32, work: &Work) -> Vec<u32> {
+        work.do_work::<J>();
+
+        let vertex = vertex as usize;
+        self.edges[self.offsets[vertex]..self.offsets[vertex + 1]].to_vec()
+    }
+}
+
+/// Claims all neighbors that haven't been visited yet, returning the ones claimed.
+fn claim_unvisited(visited: &[AtomicBool], neighbors: Vec<u32>) -> Vec<u32> {
+    neighbors
+        .into_iter()
+        .filter(|&u| !visited[u as usize].swap(true, Ordering::Relaxed))
+        .collect()
+}
+
+fn concat(mut v1: Vec<u32>, mut v2: Vec<u32>) -> Vec<u32> {
+    if v1.len() < v2.len() {
+        std::mem::swap(&mut v1, &mut v2);
+    }
+
+    v1.extend(v2);
+    v1
+}
+
+/// Level synchronous BFS, the adjacency lists of all vertices in a frontier are fetched in
+/// parallel.
+#[must_use]
+pub fn bfs<J: Joiner>(graph: &Graph, source: u32, work: &Work) -> TraversalStats {
+    let visited: Vec<AtomicBool> = (0..graph.num_vertices())
+        .map(|_| AtomicBool::new(false))
+        .collect();
+    visited[source as usize].store(true, Ordering::Relaxed);
+
+    let mut stats = TraversalStats::default();
+    let mut frontier = vec![source];
+
+    while !frontier.is_empty() {
+        stats.vertices_visited += frontier.len() as u64;
+
+        let expand = |&mut v: &mut u32| {
+            let neighbors = graph.fetch_adjacency::<J>(v, work);
+            (neighbors.len() as u64, claim_unvisited(&visited, neighbors))
+        };
+        let reduce =
+            |(e1, f1): (u64, Vec<u32>), (e2, f2): (u64, Vec<u32>)| (e1 + e2, concat(f1, f2));
+        let identity = || (0, Vec::new());
+
+        let (edges_examined, next_frontier) =
+            map_reduce::<J, _, _, _, _, _>(&mut frontier, &expand, &reduce, &identity);
+
+        stats.edges_examined += edges_examined;
+        if !next_frontier.is_empty() {
+            stats.depth += 1;
+        }
+        frontier = next_frontier;
+    }
+
+    stats
+}
+
+/// Parallel depth first traversal: a vertex claims all its unvisited neighbors at once and then
+/// recursively traverses from all of them in parallel. Recursion depth grows with the depth of the
+/// resulting traversal tree, so large graphs need large stacks.
+#[must_use]
+pub fn dfs<J: Joiner>(graph: &Graph, source: u32, work: &Work) -> TraversalStats {
+    let visited: Vec<AtomicBool> = (0..graph.num_vertices())
+        .map(|_| AtomicBool::new(false))
+        .collect();
+    visited[source as usize].store(true, Ordering::Relaxed);
+
+    dfs_visit::<J>(graph, &visited, source, 0, work)
+}
+
+fn dfs_visit<J: Joiner>(
+    graph: &Graph,
+    visited: &[AtomicBool],
+    vertex: u32,
+    depth: u32,
+    work: &Work,
+) -> TraversalStats {
+    let neighbors = graph.fetch_adjacency::<J>(vertex, work);
+    let edges_examined = neighbors.len() as u64;
+    let mut children = claim_unvisited(visited, neighbors);
+
+    let map = |&mut child: &mut u32| dfs_visit::<J>(graph, visited, child, depth + 1, work);
+    let stats = map_reduce::<J, _, _, _, _, _>(
+        &mut children,
+        &map,
+        &TraversalStats::combine,
+        &TraversalStats::default,
+    );
+
+    TraversalStats {
+        vertices_visited: stats.vertices_visited + 1,
+        edges_examined: stats.edges_examined + edges_examined,
+        depth: stats.depth.max(depth),
+    }
+}
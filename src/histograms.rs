@@ -0,0 +1,146 @@
+use hdrhistogram::Histogram;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Histograms of every thread that has recorded anything, merged by `snapshot`.
+static THREAD_HISTOGRAMS: Mutex<Vec<Arc<Mutex<LatencyHistograms>>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Only ever contended while taking a snapshot
+    static LOCAL: Arc<Mutex<LatencyHistograms>> = {
+        let local = Arc::new(Mutex::new(LatencyHistograms::new()));
+        THREAD_HISTOGRAMS.lock().unwrap().push(Arc::clone(&local));
+        local
+    };
+}
+
+/// Latency histograms in microseconds.
+pub struct LatencyHistograms {
+    /// Latency leaves asked for
+    pub requested_latency: Histogram<u64>,
+    /// Latency leaves actually observed, from suspending until resuming
+    pub actual_latency: Histogram<u64>,
+    /// Time spent in leaves that computed instead of incurring latency
+    pub leaf_compute: Histogram<u64>,
+    /// Time from a suspended `FutureJob` becoming ready until its continuation runs again
+    pub ready_to_resume: Histogram<u64>,
+    /// Whole runs, e.g. a benchmark iteration or a request
+    pub run: Histogram<u64>,
+}
+
+impl LatencyHistograms {
+    fn new() -> Self {
+        let histogram = || Histogram::new(3).unwrap();
+
+        LatencyHistograms {
+            requested_latency: histogram(),
+            actual_latency: histogram(),
+            leaf_compute: histogram(),
+            ready_to_resume: histogram(),
+            run: histogram(),
+        }
+    }
+
+    fn add(&mut self, other: &LatencyHistograms) {
+        self.requested_latency
+            .add(&other.requested_latency)
+            .unwrap();
+        self.actual_latency.add(&other.actual_latency).unwrap();
+        self.leaf_compute.add(&other.leaf_compute).unwrap();
+        self.ready_to_resume.add(&other.ready_to_resume).unwrap();
+        self.run.add(&other.run).unwrap();
+    }
+}
+
+impl std::fmt::Display for LatencyHistograms {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let histograms = [
+            ("requested latency", &self.requested_latency),
+            ("actual latency", &self.actual_latency),
+            ("leaf compute", &self.leaf_compute),
+            ("ready to resume", &self.ready_to_resume),
+            ("run", &self.run),
+        ];
+
+        for (name, histogram) in histograms {
+            if histogram.is_empty() {
+                continue;
+            }
+
+            writeln!(
+                f,
+                "{:<18} count: {} min: {}us p50: {}us p99: {}us p999: {}us max: {}us",
+                name,
+                histogram.len(),
+                histogram.min(),
+                histogram.value_at_quantile(0.5),
+                histogram.value_at_quantile(0.99),
+                histogram.value_at_quantile(0.999),
+                histogram.max()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts recording histograms. Recording is off by default, so leaves don't pay for it.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Merges the histograms recorded by all threads so far.
+#[must_use]
+pub fn snapshot() -> LatencyHistograms {
+    let mut merged = LatencyHistograms::new();
+
+    for histograms in THREAD_HISTOGRAMS.lock().unwrap().iter() {
+        merged.add(&histograms.lock().unwrap());
+    }
+
+    merged
+}
+
+/// Clears all histograms, e.g. between benchmark configurations.
+pub fn reset() {
+    for histograms in THREAD_HISTOGRAMS.lock().unwrap().iter() {
+        *histograms.lock().unwrap() = LatencyHistograms::new();
+    }
+}
+
+fn record(select: impl FnOnce(&mut LatencyHistograms) -> &mut Histogram<u64>, duration: Duration) {
+    if !is_enabled() {
+        return;
+    }
+
+    LOCAL.with(|local| {
+        select(&mut local.lock().unwrap())
+            .record(duration.as_micros() as u64)
+            .unwrap(); // histograms auto resize
+    });
+}
+
+pub(crate) fn record_latency(requested: Duration, actual: Duration) {
+    record(|h| &mut h.requested_latency, requested);
+    record(|h| &mut h.actual_latency, actual);
+}
+
+pub(crate) fn record_leaf_compute(duration: Duration) {
+    record(|h| &mut h.leaf_compute, duration);
+}
+
+pub(crate) fn record_ready_to_resume(duration: Duration) {
+    record(|h| &mut h.ready_to_resume, duration);
+}
+
+pub fn record_run(duration: Duration) {
+    record(|h| &mut h.run, duration);
+}
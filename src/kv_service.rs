@@ -0,0 +1,173 @@
+use crate::{block_on_future, Joiner};
+use async_io::Async;
+use futures::{AsyncReadExt, AsyncWriteExt};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Stub key-value server on the loopback interface. Every request is a little endian `u64` key,
+/// answered with a `u64` value after `service_ms`. At most `concurrency` requests are serviced at
+/// the same time, further requests queue up until a slot is free. Every connection has its own
+/// server thread, so clients can keep connections open between requests.
+pub struct KvServer {
+    addr: SocketAddr,
+}
+
+impl KvServer {
+    /// Starts the server on an ephemeral port of 127.0.0.1. Server threads are detached and live
+    /// until the process exits.
+    pub fn start(service_ms: u64, concurrency: usize) -> io::Result<Self> {
+        assert!(
+            concurrency > 0,
+            "KV server needs at least one server thread"
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let slots = Arc::new(Slots::new(concurrency));
+        let service_delay = Duration::from_millis(service_ms);
+
+        thread::Builder::new()
+            .name("kv-server-acceptor".to_string())
+            .spawn(move || {
+                for (i, stream) in listener.incoming().flatten().enumerate() {
+                    let slots = Arc::clone(&slots);
+
+                    // A client hanging up is not the server's problem
+                    let _ = thread::Builder::new()
+                        .name(format!("kv-server-{}", i))
+                        .spawn(move || serve_connection(stream, &slots, service_delay));
+                }
+            })?;
+
+        Ok(KvServer { addr })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+/// Counting semaphore over the requests a server may service at the same time.
+struct Slots {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Slots {
+    fn new(count: usize) -> Self {
+        Slots {
+            free: Mutex::new(count),
+            freed: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut free = self
+            .freed
+            .wait_while(self.free.lock().unwrap(), |free| *free == 0)
+            .unwrap();
+        *free -= 1;
+    }
+
+    fn release(&self) {
+        *self.free.lock().unwrap() += 1;
+        self.freed.notify_one();
+    }
+}
+
+fn serve_connection(
+    mut stream: TcpStream,
+    slots: &Slots,
+    service_delay: Duration,
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut key = [0; 8];
+
+    loop {
+        match stream.read_exact(&mut key) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        slots.acquire();
+        thread::sleep(service_delay);
+        slots.release();
+
+        let value = u64::from_le_bytes(key).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        stream.write_all(&value.to_le_bytes())?;
+    }
+}
+
+/// Idle connections to every server, so requests don't pay for a handshake each. A connection is
+/// only put back after a successful request, so broken connections are dropped.
+static IDLE_CONNECTIONS: Mutex<BTreeMap<SocketAddr, Vec<TcpStream>>> = Mutex::new(BTreeMap::new());
+
+fn take_idle_connection(addr: SocketAddr) -> Option<TcpStream> {
+    IDLE_CONNECTIONS.lock().unwrap().get_mut(&addr)?.pop()
+}
+
+fn put_idle_connection(addr: SocketAddr, stream: TcpStream) {
+    IDLE_CONNECTIONS
+        .lock()
+        .unwrap()
+        .entry(addr)
+        .or_default()
+        .push(stream);
+}
+
+/// Requests `key` from the KV server at `addr`, over an idle connection if there is one. With a
+/// latency hiding joiner the request is driven as a `FutureJob` on top of `async_io`, so the worker
+/// thread is only woken up again once the socket is readable. Otherwise the request blocks the
+/// thread.
+pub fn request<J: Joiner>(addr: SocketAddr, key: u64) -> io::Result<u64> {
+    if J::is_latency_hiding() {
+        block_on_future::<J, _>(request_async(addr, key))
+    } else {
+        request_blocking(addr, key)
+    }
+}
+
+async fn request_async(addr: SocketAddr, key: u64) -> io::Result<u64> {
+    let mut stream = match take_idle_connection(addr) {
+        Some(stream) => Async::new(stream)?,
+        None => {
+            let stream = Async::<TcpStream>::connect(addr).await?;
+            stream.get_ref().set_nodelay(true)?;
+            stream
+        }
+    };
+    stream.write_all(&key.to_le_bytes()).await?;
+
+    let mut value = [0; 8];
+    stream.read_exact(&mut value).await?;
+
+    put_idle_connection(addr, stream.into_inner()?);
+    Ok(u64::from_le_bytes(value))
+}
+
+fn request_blocking(addr: SocketAddr, key: u64) -> io::Result<u64> {
+    let mut stream = match take_idle_connection(addr) {
+        Some(stream) => {
+            // Left non-blocking if an async request used it last
+            stream.set_nonblocking(false)?;
+            stream
+        }
+        None => {
+            let stream = TcpStream::connect(addr)?;
+            stream.set_nodelay(true)?;
+            stream
+        }
+    };
+    stream.write_all(&key.to_le_bytes())?;
+
+    let mut value = [0; 8];
+    stream.read_exact(&mut value)?;
+
+    put_idle_connection(addr, stream);
+    Ok(u64::from_le_bytes(value))
+}
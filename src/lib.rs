@@ -2,13 +2,39 @@ use async_io::Timer;
 use clap::ArgEnum;
 use pin_utils::pin_mut;
 use rand::prelude::*;
+use service_model::ServiceModel;
 use std::cell::UnsafeCell;
+use std::future::Future;
+use std::net::SocketAddr;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+pub mod affinity;
+pub mod analysis;
+pub mod baseline;
 pub mod fib;
+pub mod graph;
+pub mod histograms;
+pub mod kv_service;
 pub mod map_reduce;
+pub mod measure;
+pub mod metadata;
+pub mod migration;
+pub mod model;
+pub mod open_loop;
+pub mod perf;
+pub mod plot;
 pub mod quicksort;
+pub mod report;
+pub mod service_model;
+pub mod stack;
+pub mod thread_pool;
+pub mod utilization;
+pub mod uts;
+
+#[cfg(feature = "count-alloc")]
+#[global_allocator]
+static ALLOCATOR: measure::CountingAllocator = measure::CountingAllocator;
 
 thread_local! {
     static RNG: UnsafeCell<ThreadRng> = UnsafeCell::new(rand::thread_rng());
@@ -51,6 +77,17 @@ pub trait Joiner {
         B: FnOnce() -> RB + Send,
         RA: Send,
         RB: Send;
+
+    /// Like `join`, but each closure is passed whether it was stolen (migrated) by another thread.
+    fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce(bool) -> RA + Send,
+        B: FnOnce(bool) -> RB + Send,
+        RA: Send,
+        RB: Send;
+
+    #[must_use]
+    fn current_num_threads() -> usize;
 }
 
 pub struct Serial;
@@ -78,6 +115,23 @@ impl Joiner for Serial {
 
         (ra, rb)
     }
+
+    fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce(bool) -> RA + Send,
+        B: FnOnce(bool) -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        let ra = oper_a(false);
+        let rb = oper_b(false);
+
+        (ra, rb)
+    }
+
+    fn current_num_threads() -> usize {
+        1
+    }
 }
 
 pub struct Parallel;
@@ -102,6 +156,20 @@ impl Joiner for Parallel {
     {
         rayon::join(oper_a, oper_b)
     }
+
+    fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce(bool) -> RA + Send,
+        B: FnOnce(bool) -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        rayon::join_context(|ctx| oper_a(ctx.migrated()), |ctx| oper_b(ctx.migrated()))
+    }
+
+    fn current_num_threads() -> usize {
+        rayon::current_num_threads()
+    }
 }
 
 pub struct ParallelOldRayon;
@@ -126,6 +194,20 @@ impl Joiner for ParallelOldRayon {
     {
         rayon_old::join(oper_a, oper_b)
     }
+
+    fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce(bool) -> RA + Send,
+        B: FnOnce(bool) -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        rayon_old::join_context(|ctx| oper_a(ctx.migrated()), |ctx| oper_b(ctx.migrated()))
+    }
+
+    fn current_num_threads() -> usize {
+        rayon_old::current_num_threads()
+    }
 }
 
 pub struct ParallelLH;
@@ -150,25 +232,105 @@ impl Joiner for ParallelLH {
     {
         rayon::join(oper_a, oper_b)
     }
+
+    fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce(bool) -> RA + Send,
+        B: FnOnce(bool) -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        rayon::join_context(|ctx| oper_a(ctx.migrated()), |ctx| oper_b(ctx.migrated()))
+    }
+
+    fn current_num_threads() -> usize {
+        rayon::current_num_threads()
+    }
 }
 
-/// Builds Rayon global threadpool. Stack size specified in multiples of MB.
-pub fn build_global_threadpool(cores: Option<usize>, stack_size: Option<usize>) {
-    let pool_builder = rayon::ThreadPoolBuilder::new();
+/// Generous estimate of the stack used per level of workload recursion, including Rayon's join
+/// frames (debug builds have much larger frames than release builds).
+const AUTO_STACK_BYTES_PER_LEVEL: usize = 16 * 1024;
+/// A latency hiding worker keeps the frames of suspended jobs alive below the jobs it picks up
+/// meanwhile, so its stack holds several workload call chains at once.
+const AUTO_STACK_SUSPENDED_CHAINS: usize = 4;
+const AUTO_STACK_MIN_MB: usize = 2;
+const AUTO_STACK_MAX_MB: usize = 256;
 
-    let pool_builder = if let Some(cores) = cores {
-        pool_builder.num_threads(cores)
-    } else {
-        pool_builder
-    };
+#[derive(Copy, Clone)]
+pub enum StackSize {
+    /// In multiples of MB
+    Mb(usize),
+    /// Sized based on the recursion depth of the workload, within 2 to 256 MB. The depth is an
+    /// upper bound, so deep but narrow workloads may get much larger stacks than they use
+    Auto,
+}
 
-    let pool_builder = if let Some(stack_size) = stack_size {
-        pool_builder.stack_size(stack_size * 1024 * 1024) // in multiple of MB
-    } else {
-        pool_builder
-    };
+impl StackSize {
+    /// Stack size in MB, for a workload recursing `depth` levels deep.
+    pub fn mb(&self, depth: u32) -> usize {
+        match self {
+            StackSize::Mb(mb) => *mb,
+            StackSize::Auto => {
+                let bytes =
+                    depth as usize * AUTO_STACK_BYTES_PER_LEVEL * AUTO_STACK_SUSPENDED_CHAINS;
+                let mb = bytes.div_ceil(1024 * 1024);
+
+                if mb > AUTO_STACK_MAX_MB {
+                    eprintln!(
+                        "Recursion depth {} asks for {} MB stacks, clamping to {} MB. \
+                         Pass a larger --stack-size if workers overflow",
+                        depth, mb, AUTO_STACK_MAX_MB
+                    );
+                }
+
+                mb.clamp(AUTO_STACK_MIN_MB, AUTO_STACK_MAX_MB)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for StackSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StackSize::Mb(mb) => write!(f, "{}", mb),
+            StackSize::Auto => write!(f, "Auto"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseStackSizeError {
+    Zero,
+    ParseError,
+}
+
+impl std::error::Error for ParseStackSizeError {}
+
+impl std::fmt::Display for ParseStackSizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseStackSizeError::Zero => {
+                write!(f, "Stack size must be at least 1 MB")
+            }
+            ParseStackSizeError::ParseError => {
+                write!(f, "Argument for stack size could not be parsed")
+            }
+        }
+    }
+}
+
+/// Parses either a stack size in MB, or "auto".
+pub fn parse_stack_size(s: &str) -> Result<StackSize, ParseStackSizeError> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(StackSize::Auto);
+    }
 
-    pool_builder.build_global().unwrap();
+    match usize::from_str(s) {
+        Ok(0) => Err(ParseStackSizeError::Zero),
+        Ok(mb) => Ok(StackSize::Mb(mb)),
+        Err(_) => Err(ParseStackSizeError::ParseError),
+    }
 }
 
 #[derive(Debug)]
@@ -205,6 +367,8 @@ pub enum Work {
     DoNothing,
     PureLatency { work_ms: u64 },
     LatencyOrCompute { work_ms: u64, latency_p: f32 },
+    RemoteRequest { addr: SocketAddr }, // request to a `kv_service::KvServer` listening on `addr`
+    QueuedService { model: &'static ServiceModel }, // slows down as more requests are queued
 }
 
 impl Work {
@@ -230,9 +394,42 @@ impl Work {
                     inject_latency::<J>(*work_ms)
                 } else {
                     // Pretend to "compute"
+                    let start = histograms::is_enabled().then(Instant::now);
                     std::thread::sleep(Duration::from_millis(*work_ms));
+
+                    if let Some(start) = start {
+                        histograms::record_leaf_compute(start.elapsed());
+                    }
                 }
             }
+            Work::RemoteRequest { addr } => {
+                remote_request::<J>(*addr);
+            }
+            Work::QueuedService { model } => {
+                model.request::<J>();
+            }
+        }
+    }
+
+    /// Like `do_work`, but when no latency is incurred nothing is done at all (instead of
+    /// pretending to compute). Models a lookup that is usually served locally.
+    pub fn maybe_incur_latency<J: Joiner>(&self) {
+        match self {
+            Work::DoNothing => {}
+            Work::PureLatency { work_ms } => {
+                inject_latency::<J>(*work_ms);
+            }
+            Work::LatencyOrCompute { work_ms, latency_p } => {
+                if incurs_latency(*latency_p) {
+                    inject_latency::<J>(*work_ms)
+                }
+            }
+            Work::RemoteRequest { addr } => {
+                remote_request::<J>(*addr);
+            }
+            Work::QueuedService { model } => {
+                model.request::<J>();
+            }
         }
     }
 }
@@ -247,11 +444,66 @@ fn incurs_latency(p: f32) -> bool {
 }
 
 fn inject_latency<J: Joiner>(latency_ms: u64) {
+    let latency = Duration::from_millis(latency_ms);
+    // Reading the clock on every leaf only pays off if histograms are recorded
+    let start = histograms::is_enabled().then(Instant::now);
+
     if J::is_latency_hiding() {
-        let future_job = rayon::FutureJob::new(Timer::after(Duration::from_millis(latency_ms)));
-        pin_mut!(future_job);
-        future_job.spawn().await_future_job();
+        let suspended_on = rayon::current_thread_index();
+        // Timer::after would read the clock anyway
+        let deadline = start.unwrap_or_else(Instant::now) + latency;
+
+        {
+            let future_job = rayon::FutureJob::new(async {
+                Timer::at(deadline).await;
+            });
+            pin_mut!(future_job);
+            future_job.spawn().await_future_job();
+        }
+
+        // From the timer firing, so waking the job and waiting for a worker to poll it count too
+        if start.is_some() {
+            histograms::record_ready_to_resume(deadline.elapsed());
+        }
+        migration::record_resume(suspended_on, rayon::current_thread_index());
+    } else {
+        std::thread::sleep(latency);
+    }
+
+    if let Some(start) = start {
+        histograms::record_latency(latency, start.elapsed());
+    }
+}
+
+fn remote_request<J: Joiner>(addr: SocketAddr) {
+    let key = RNG.with(|rng| unsafe { &mut *rng.get() }.gen());
+
+    // A failed request still took its time, so report it and let the run go on
+    if let Err(e) = kv_service::request::<J>(addr, key) {
+        eprintln!("Request to KV server at {} failed: {}", addr, e);
+    }
+}
+
+/// Drives a future to completion and returns its output. With a latency hiding joiner the future
+/// is spawned as a `FutureJob`, so the worker thread can work on other jobs while the future is
+/// pending. Otherwise the calling thread simply blocks on the future.
+pub fn block_on_future<J, F>(future: F) -> F::Output
+where
+    J: Joiner,
+    F: Future + Send,
+    F::Output: Send,
+{
+    if J::is_latency_hiding() {
+        let mut output = None;
+
+        {
+            let future_job = rayon::FutureJob::new(async { output = Some(future.await) });
+            pin_mut!(future_job);
+            future_job.spawn().await_future_job();
+        }
+
+        output.unwrap()
     } else {
-        std::thread::sleep(Duration::from_millis(latency_ms));
+        futures::executor::block_on(future)
     }
 }
@@ -1,4 +1,91 @@
-use crate::Joiner;
+use crate::{block_on_future, Joiner};
+use std::future::Future;
+use std::str::FromStr;
+
+/// Controls how far `map_reduce` splits its input before processing items sequentially.
+#[derive(Copy, Clone)]
+pub enum Grain {
+    /// Split down to chunks of at most this many items.
+    Fixed(usize),
+    /// Split into roughly as many chunks as there are threads, and split further only when a chunk
+    /// gets stolen (the same heuristic as Rayon's adaptive splitter).
+    Adaptive,
+}
+
+impl std::fmt::Display for Grain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Grain::Fixed(grain) => write!(f, "{}", grain),
+            Grain::Adaptive => write!(f, "Adaptive"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseGrainError {
+    Zero,
+    ParseError,
+}
+
+impl std::error::Error for ParseGrainError {}
+
+impl std::fmt::Display for ParseGrainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseGrainError::Zero => {
+                write!(f, "Grain size must be at least 1")
+            }
+            ParseGrainError::ParseError => {
+                write!(f, "Argument for grain could not be parsed")
+            }
+        }
+    }
+}
+
+/// Parses either a fixed grain size, or "adaptive".
+pub fn parse_grain(s: &str) -> Result<Grain, ParseGrainError> {
+    if s.eq_ignore_ascii_case("adaptive") {
+        return Ok(Grain::Adaptive);
+    }
+
+    match usize::from_str(s) {
+        Ok(0) => Err(ParseGrainError::Zero),
+        Ok(grain) => Ok(Grain::Fixed(grain)),
+        Err(_) => Err(ParseGrainError::ParseError),
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Splitter {
+    grain: Grain,
+    splits: usize,
+}
+
+impl Splitter {
+    fn new<J: Joiner>(grain: Grain) -> Self {
+        Splitter {
+            grain,
+            splits: J::current_num_threads(),
+        }
+    }
+
+    fn try_split<J: Joiner>(&mut self, len: usize, migrated: bool) -> bool {
+        match self.grain {
+            Grain::Fixed(grain) => len > grain.max(1),
+            Grain::Adaptive if len <= 1 => false,
+            Grain::Adaptive if migrated => {
+                // We were stolen, so there are idle threads: reset the number of splits
+                self.splits = std::cmp::max(J::current_num_threads(), self.splits / 2);
+                true
+            }
+            Grain::Adaptive if self.splits > 0 => {
+                self.splits /= 2;
+                true
+            }
+            Grain::Adaptive => false,
+        }
+    }
+}
 
 pub fn map_reduce<J, T, MAP, REDUCE, ID, R>(
     items: &mut [T],
@@ -14,24 +101,101 @@ where
     ID: Fn() -> R + Sync,
     R: Send,
 {
-    if items.len() == 0 {
-        return identity();
-    } else if items.len() == 1 {
-        return map(&mut items[0]);
+    map_reduce_with_grain::<J, _, _, _, _, _>(items, Grain::Fixed(1), map, reduce, identity)
+}
+
+/// Like `map_reduce`, but leaves process chunks of items sequentially, with the chunk size
+/// determined by `grain`.
+pub fn map_reduce_with_grain<J, T, MAP, REDUCE, ID, R>(
+    items: &mut [T],
+    grain: Grain,
+    map: &MAP,
+    reduce: &REDUCE,
+    identity: &ID,
+) -> R
+where
+    J: Joiner,
+    T: Send,
+    MAP: Fn(&mut T) -> R + Sync,
+    REDUCE: Fn(R, R) -> R + Sync,
+    ID: Fn() -> R + Sync,
+    R: Send,
+{
+    split_map_reduce::<J, _, _, _, _, _>(
+        items,
+        Splitter::new::<J>(grain),
+        false,
+        map,
+        reduce,
+        identity,
+    )
+}
+
+fn split_map_reduce<J, T, MAP, REDUCE, ID, R>(
+    items: &mut [T],
+    mut splitter: Splitter,
+    migrated: bool,
+    map: &MAP,
+    reduce: &REDUCE,
+    identity: &ID,
+) -> R
+where
+    J: Joiner,
+    T: Send,
+    MAP: Fn(&mut T) -> R + Sync,
+    REDUCE: Fn(R, R) -> R + Sync,
+    ID: Fn() -> R + Sync,
+    R: Send,
+{
+    if !splitter.try_split::<J>(items.len(), migrated) {
+        return items
+            .iter_mut()
+            .map(map)
+            .reduce(reduce)
+            .unwrap_or_else(identity);
     }
 
     let (s1, s2) = items.split_at_mut(items.len() / 2);
-    let (ra, rb) = J::join(
-        || map_reduce::<J, _, _, _, _, _>(s1, map, reduce, identity),
-        || map_reduce::<J, _, _, _, _, _>(s2, map, reduce, identity),
+    let (ra, rb) = J::join_context(
+        |migrated| {
+            split_map_reduce::<J, _, _, _, _, _>(s1, splitter, migrated, map, reduce, identity)
+        },
+        |migrated| {
+            split_map_reduce::<J, _, _, _, _, _>(s2, splitter, migrated, map, reduce, identity)
+        },
     );
 
     reduce(ra, rb)
 }
 
+/// Like `map_reduce_with_grain`, but with an async map function. Each mapped future is driven with
+/// `block_on_future`, i.e. as a `FutureJob` if `J` is latency hiding, and blocked on otherwise.
+pub fn map_reduce_async<J, T, MAP, FUT, REDUCE, ID, R>(
+    items: &mut [T],
+    grain: Grain,
+    map: &MAP,
+    reduce: &REDUCE,
+    identity: &ID,
+) -> R
+where
+    J: Joiner,
+    T: Send,
+    MAP: Fn(&mut T) -> FUT + Sync,
+    FUT: Future<Output = R> + Send,
+    REDUCE: Fn(R, R) -> R + Sync,
+    ID: Fn() -> R + Sync,
+    R: Send,
+{
+    let map = |item: &mut T| block_on_future::<J, _>(map(item));
+
+    map_reduce_with_grain::<J, _, _, _, _, _>(items, grain, &map, reduce, identity)
+}
+
 pub mod map_reduce_fib {
     use crate::fib::fib;
     use crate::{Joiner, Work};
+    use async_io::Timer;
+    use std::time::Duration;
 
     pub fn map<J: Joiner>(n: u32, work: &Work, serial_cutoff: u32) -> u32 {
         // Possibly do work, if specified, but only in root nodes of computation DAG
@@ -43,6 +207,19 @@ pub mod map_reduce_fib {
         fib::<J>(n, &fib_work, serial_cutoff).0
     }
 
+    /// Async counterpart of `map`, where the latency is an awaited timer instead of going through
+    /// `Work`.
+    pub async fn async_map<J: Joiner>(n: u32, latency_ms: Option<u64>, serial_cutoff: u32) -> u32 {
+        if let Some(latency_ms) = latency_ms {
+            Timer::after(Duration::from_millis(latency_ms)).await;
+        }
+
+        // Do only pure compute in fibonacci
+        let fib_work = Work::new(None, None);
+
+        fib::<J>(n, &fib_work, serial_cutoff).0
+    }
+
     pub fn reduce(f1: u32, f2: u32) -> u32 {
         ((f1).wrapping_add(f2)) % 1_000_000_000
     }
@@ -51,3 +228,113 @@ pub mod map_reduce_fib {
         0
     }
 }
+
+pub mod map_reduce_word_count {
+    use crate::{Joiner, Work};
+    use rand::distributions::{Alphanumeric, Distribution, WeightedIndex};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    const MIN_WORD_LEN: usize = 2;
+    const MAX_WORD_LEN: usize = 12;
+
+    pub type WordCounts = HashMap<String, usize>;
+
+    #[derive(Debug)]
+    pub enum ParseVocabularySizeError {
+        Zero,
+        ParseError,
+    }
+
+    impl std::error::Error for ParseVocabularySizeError {}
+
+    impl std::fmt::Display for ParseVocabularySizeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                ParseVocabularySizeError::Zero => {
+                    write!(f, "Vocabulary must have at least 1 word")
+                }
+                ParseVocabularySizeError::ParseError => {
+                    write!(f, "Argument for vocabulary size could not be parsed")
+                }
+            }
+        }
+    }
+
+    pub fn parse_vocabulary_size(s: &str) -> Result<usize, ParseVocabularySizeError> {
+        match s.parse::<usize>() {
+            Ok(0) => Err(ParseVocabularySizeError::Zero),
+            Ok(vocabulary_size) => Ok(vocabulary_size),
+            Err(_) => Err(ParseVocabularySizeError::ParseError),
+        }
+    }
+
+    /// Generates `documents` documents of `words_per_document` words each. Words are drawn from a
+    /// vocabulary of `vocabulary_size` random words following a Zipf distribution, like natural
+    /// language. The same seed always generates the same corpus.
+    pub fn generate_corpus(
+        seed: u64,
+        documents: usize,
+        words_per_document: usize,
+        vocabulary_size: usize,
+    ) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let vocabulary: Vec<String> = (0..vocabulary_size)
+            .map(|_| {
+                let len = rng.gen_range(MIN_WORD_LEN..=MAX_WORD_LEN);
+                (&mut rng)
+                    .sample_iter(Alphanumeric)
+                    .take(len)
+                    .map(char::from)
+                    .collect()
+            })
+            .collect();
+
+        let zipf = WeightedIndex::new((1..=vocabulary_size).map(|rank| 1.0 / rank as f64))
+            .expect("Vocabulary must not be empty");
+
+        (0..documents)
+            .map(|_| {
+                (0..words_per_document)
+                    .map(|_| vocabulary[zipf.sample(&mut rng)].as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    pub fn map<J: Joiner>(document: &str, work: &Work) -> WordCounts {
+        // Possibly do work, if specified, but only in root nodes of computation DAG
+        work.do_work::<J>();
+
+        let mut counts = WordCounts::new();
+
+        for word in document.split_whitespace() {
+            *counts.entry(word.to_owned()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Merges the smaller map into the larger one, so the cost of a reduce grows with the number of
+    /// distinct words seen so far.
+    pub fn reduce(c1: WordCounts, c2: WordCounts) -> WordCounts {
+        let (mut larger, smaller) = if c1.len() >= c2.len() {
+            (c1, c2)
+        } else {
+            (c2, c1)
+        };
+
+        for (word, count) in smaller {
+            *larger.entry(word).or_insert(0) += count;
+        }
+
+        larger
+    }
+
+    pub fn identity() -> WordCounts {
+        WordCounts::new()
+    }
+}
@@ -0,0 +1,213 @@
+use crate::perf::{self, PerfReading};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "count-alloc")]
+pub use counting::CountingAllocator;
+
+/// Heap usage as seen by `CountingAllocator`, only available with the `count-alloc` feature.
+#[derive(Copy, Clone)]
+pub struct AllocStats {
+    pub allocations: u64,
+    /// Total bytes allocated
+    pub bytes: u64,
+    /// Largest number of bytes live at the same time
+    pub peak_heap: u64,
+}
+
+/// Resident set size in KB, read from `/proc/self/status`.
+#[derive(Copy, Clone)]
+pub struct Rss {
+    pub current_kb: u64,
+    pub peak_kb: u64,
+}
+
+/// Measures wall-clock time, allocations and RSS of a run, plus perf counters if they were opened
+/// with `perf::open_counters`.
+pub struct Measurement {
+    start: Instant,
+    alloc_start: Option<AllocStats>,
+}
+
+pub struct MeasurementReport {
+    pub wall_clock: Duration,
+    /// Allocations during the run, `None` without the `count-alloc` feature
+    pub alloc: Option<AllocStats>,
+    /// At the end of the run, `None` if `/proc/self/status` isn't available
+    pub rss: Option<Rss>,
+    /// `None` unless perf counters were opened
+    pub perf: Option<PerfReading>,
+}
+
+impl Measurement {
+    pub fn start() -> Self {
+        reset_peak_heap();
+
+        if let Some(counters) = perf::counters() {
+            counters.start();
+        }
+
+        Measurement {
+            start: Instant::now(),
+            alloc_start: alloc_stats(),
+        }
+    }
+
+    pub fn finish(&self) -> MeasurementReport {
+        let wall_clock = self.start.elapsed();
+        let perf = perf::counters().map(|counters| counters.stop());
+
+        let alloc = alloc_stats()
+            .zip(self.alloc_start)
+            .map(|(end, start)| AllocStats {
+                allocations: end.allocations - start.allocations,
+                bytes: end.bytes - start.bytes,
+                peak_heap: end.peak_heap,
+            });
+
+        MeasurementReport {
+            wall_clock,
+            alloc,
+            rss: rss(),
+            perf,
+        }
+    }
+}
+
+impl std::fmt::Display for MeasurementReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "wall clock: {:?}", self.wall_clock)?;
+
+        if let Some(alloc) = self.alloc {
+            write!(
+                f,
+                " allocations: {} allocated: {} KB peak heap: {} KB",
+                alloc.allocations,
+                alloc.bytes / 1024,
+                alloc.peak_heap / 1024
+            )?;
+        }
+
+        if let Some(rss) = self.rss {
+            write!(
+                f,
+                " rss: {} KB peak rss: {} KB",
+                rss.current_kb, rss.peak_kb
+            )?;
+        }
+
+        if let Some(perf) = &self.perf {
+            write!(f, " {}", perf)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads VmRSS and VmHWM from `/proc/self/status`.
+#[must_use]
+pub fn rss() -> Option<Rss> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+    };
+
+    Some(Rss {
+        current_kb: field("VmRSS:")?,
+        peak_kb: field("VmHWM:")?,
+    })
+}
+
+#[cfg(feature = "count-alloc")]
+#[must_use]
+pub fn alloc_stats() -> Option<AllocStats> {
+    Some(counting::stats())
+}
+
+#[cfg(not(feature = "count-alloc"))]
+#[must_use]
+pub fn alloc_stats() -> Option<AllocStats> {
+    None
+}
+
+/// Restarts tracking the peak heap from the number of bytes currently live.
+pub fn reset_peak_heap() {
+    #[cfg(feature = "count-alloc")]
+    counting::reset_peak();
+}
+
+#[cfg(feature = "count-alloc")]
+mod counting {
+    use super::AllocStats;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static BYTES: AtomicU64 = AtomicU64::new(0);
+    static LIVE: AtomicU64 = AtomicU64::new(0);
+    static PEAK: AtomicU64 = AtomicU64::new(0);
+
+    /// Wraps the system allocator, counting allocations and live bytes.
+    pub struct CountingAllocator;
+
+    impl CountingAllocator {
+        fn record_alloc(size: usize) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            BYTES.fetch_add(size as u64, Ordering::Relaxed);
+
+            let live = LIVE.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+
+        fn record_dealloc(size: usize) {
+            LIVE.fetch_sub(size as u64, Ordering::Relaxed);
+        }
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                Self::record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                Self::record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            Self::record_dealloc(layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                Self::record_dealloc(layout.size());
+                Self::record_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+
+    pub(super) fn stats() -> AllocStats {
+        AllocStats {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            bytes: BYTES.load(Ordering::Relaxed),
+            peak_heap: PEAK.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(super) fn reset_peak() {
+        PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::affinity;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the file `write_criterion_metadata` writes into a criterion group directory.
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Environment a run was built and executed in, so results can be reproduced and compared.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub command_line: Vec<String>,
+    /// Git revision of this crate, "-dirty" if it had uncommitted changes
+    pub benchmarks_revision: String,
+    /// Git revision of the patched rayon in `../rayon`
+    pub rayon_revision: String,
+    pub rustc_version: String,
+    pub profile: String,
+    pub opt_level: String,
+    pub count_alloc: bool,
+    pub hostname: Option<String>,
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+    pub online_cpus: usize,
+    /// CPUs the process is allowed to run on
+    pub allowed_cpus: Option<usize>,
+    /// Distinct frequency governors over all CPUs
+    pub governors: Vec<String>,
+    /// 1, 5 and 15 minute load average when the run started
+    pub load_average: Option<[f64; 3]>,
+}
+
+impl RunMetadata {
+    pub fn collect() -> Self {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+
+        RunMetadata {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            command_line: std::env::args().collect(),
+            benchmarks_revision: env!("BENCHMARKS_REVISION").to_string(),
+            rayon_revision: env!("RAYON_REVISION").to_string(),
+            rustc_version: env!("RUSTC_VERSION").to_string(),
+            profile: env!("BUILD_PROFILE").to_string(),
+            opt_level: env!("BUILD_OPT_LEVEL").to_string(),
+            count_alloc: cfg!(feature = "count-alloc"),
+            hostname: read_trimmed("/proc/sys/kernel/hostname"),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+            cpu_model: cpuinfo_field(&cpuinfo, "model name"),
+            online_cpus: cpuinfo
+                .lines()
+                .filter(|line| line.starts_with("processor"))
+                .count(),
+            allowed_cpus: affinity::cpus().ok().map(|cpus| cpus.len()),
+            governors: governors(),
+            load_average: load_average(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Metadata is always serializable")
+    }
+
+    pub fn write_json(&self, path: impl Into<PathBuf>) -> io::Result<()> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, self.to_json())
+    }
+
+    pub fn read_json(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let json = fs::read_to_string(path.into())?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl std::fmt::Display for RunMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let or_unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".into());
+
+        writeln!(f, "command line: {}", self.command_line.join(" "))?;
+        writeln!(f, "benchmarks revision: {}", self.benchmarks_revision)?;
+        writeln!(f, "rayon revision: {}", self.rayon_revision)?;
+        writeln!(
+            f,
+            "rustc: {} profile: {} opt-level: {} count-alloc: {}",
+            self.rustc_version, self.profile, self.opt_level, self.count_alloc
+        )?;
+        writeln!(
+            f,
+            "host: {} kernel: {}",
+            or_unknown(&self.hostname),
+            or_unknown(&self.kernel)
+        )?;
+        writeln!(
+            f,
+            "cpu: {} online cpus: {} allowed cpus: {} governor: {}",
+            or_unknown(&self.cpu_model),
+            self.online_cpus,
+            self.allowed_cpus
+                .map_or_else(|| "unknown".into(), |cpus| cpus.to_string()),
+            if self.governors.is_empty() {
+                "unknown".into()
+            } else {
+                self.governors.join(",")
+            }
+        )?;
+
+        match self.load_average {
+            Some([one, five, fifteen]) => {
+                writeln!(f, "load average: {:.2} {:.2} {:.2}", one, five, fifteen)
+            }
+            None => writeln!(f, "load average: unknown"),
+        }
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn cpuinfo_field(cpuinfo: &str, name: &str) -> Option<String> {
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == name).then(|| value.trim().to_string())
+    })
+}
+
+fn governors() -> Vec<String> {
+    let governors: BTreeSet<String> = fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            fs::read_to_string(entry.path().join("cpufreq/scaling_governor"))
+                .ok()
+                .map(|governor| governor.trim().to_string())
+        })
+        .collect();
+
+    governors.into_iter().collect()
+}
+
+fn load_average() -> Option<[f64; 3]> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let mut fields = loadavg.split_whitespace().map(|field| field.parse().ok());
+
+    Some([fields.next()??, fields.next()??, fields.next()??])
+}
+
+/// Name criterion gives the directory of a group or benchmark id.
+fn criterion_dir_name(name: &str) -> String {
+    let mut dir_name: String = name
+        .chars()
+        .map(|c| match c {
+            '?' | '"' | '/' | '\\' | '*' | '<' | '>' | ':' | '|' | '^' => '_',
+            c => c,
+        })
+        .collect();
+
+    if dir_name.len() > 64 {
+        let end = (0..=64)
+            .rev()
+            .find(|&i| dir_name.is_char_boundary(i))
+            .unwrap();
+        dir_name.truncate(end);
+    }
+
+    dir_name
+}
+
+/// Directory criterion writes the results of a benchmark group to, mirroring criterion's own
+/// lookup of the output directory and escaping of group names.
+pub fn criterion_group_dir(group: &str) -> PathBuf {
+    let criterion_home = std::env::var_os("CRITERION_HOME").map(PathBuf::from);
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+
+    criterion_home
+        .unwrap_or_else(|| target_dir.join("criterion"))
+        .join(criterion_dir_name(group))
+}
+
+/// Directory of a single benchmark of a group, e.g. `BenchmarkId::new(function, parameter)`.
+pub fn criterion_benchmark_dir(group: &str, function: &str, parameter: &str) -> PathBuf {
+    criterion_group_dir(group)
+        .join(criterion_dir_name(function))
+        .join(criterion_dir_name(parameter))
+}
+
+/// Writes the metadata of the current run into the criterion directory of `group`, next to the
+/// results of its benchmarks. Failing to write it is reported but otherwise ignored, so benches
+/// still run.
+pub fn write_criterion_metadata(group: &str) {
+    let path = criterion_group_dir(group).join(METADATA_FILE);
+
+    if let Err(e) = RunMetadata::collect().write_json(&path) {
+        eprintln!("Could not write run metadata to {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{Joiner, Work};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static SUSPENSIONS: AtomicU64 = AtomicU64::new(0);
+static MIGRATIONS: AtomicU64 = AtomicU64::new(0);
+
+// Time spent reading back a leaf's working set after resuming, split by whether the leaf migrated
+static REWARM_LOCAL_NS: AtomicU64 = AtomicU64::new(0);
+static REWARM_LOCAL_COUNT: AtomicU64 = AtomicU64::new(0);
+static REWARM_MIGRATED_NS: AtomicU64 = AtomicU64::new(0);
+static REWARM_MIGRATED_COUNT: AtomicU64 = AtomicU64::new(0);
+
+pub struct MigrationStats {
+    /// `FutureJob`s suspended in latency hiding leaves
+    pub suspensions: u64,
+    /// Suspended `FutureJob`s whose continuation resumed on a different worker thread
+    pub migrations: u64,
+    pub mean_rewarm_local: Duration,
+    pub mean_rewarm_migrated: Duration,
+}
+
+impl MigrationStats {
+    pub fn migration_rate(&self) -> f64 {
+        self.migrations as f64 / self.suspensions.max(1) as f64
+    }
+}
+
+impl std::fmt::Display for MigrationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "suspensions: {} migrations: {} ({:.1}%) mean rewarm local: {:?} migrated: {:?}",
+            self.suspensions,
+            self.migrations,
+            self.migration_rate() * 100.0,
+            self.mean_rewarm_local,
+            self.mean_rewarm_migrated
+        )
+    }
+}
+
+/// Starts counting suspensions and migrations. Counting is off by default, so every latency hiding
+/// leaf doesn't pay for the shared counters.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records a suspended `FutureJob` resuming, given the worker thread indices it was suspended and
+/// resumed on.
+pub(crate) fn record_resume(suspended_on: Option<usize>, resumed_on: Option<usize>) {
+    if !is_enabled() {
+        return;
+    }
+
+    SUSPENSIONS.fetch_add(1, Ordering::Relaxed);
+
+    if suspended_on != resumed_on {
+        MIGRATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn mean(ns: &AtomicU64, count: &AtomicU64) -> Duration {
+    Duration::from_nanos(ns.load(Ordering::Relaxed) / count.load(Ordering::Relaxed).max(1))
+}
+
+pub fn stats() -> MigrationStats {
+    MigrationStats {
+        suspensions: SUSPENSIONS.load(Ordering::Relaxed),
+        migrations: MIGRATIONS.load(Ordering::Relaxed),
+        mean_rewarm_local: mean(&REWARM_LOCAL_NS, &REWARM_LOCAL_COUNT),
+        mean_rewarm_migrated: mean(&REWARM_MIGRATED_NS, &REWARM_MIGRATED_COUNT),
+    }
+}
+
+/// Clears all counters, e.g. between benchmark configurations.
+pub fn reset() {
+    for counter in [
+        &SUSPENSIONS,
+        &MIGRATIONS,
+        &REWARM_LOCAL_NS,
+        &REWARM_LOCAL_COUNT,
+        &REWARM_MIGRATED_NS,
+        &REWARM_MIGRATED_COUNT,
+    ] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Leaf with a working set of `working_set_kb`: writes the working set, does `work`, and then reads
+/// the working set back. Reading it back is timed, as it is slower when the leaf resumed on a
+/// different worker thread, whose cache doesn't hold the working set.
+pub fn memory_heavy_leaf<J: Joiner>(work: &Work, working_set_kb: usize) -> u64 {
+    let mut working_set = vec![0u64; working_set_kb * 1024 / std::mem::size_of::<u64>()];
+    for (i, x) in working_set.iter_mut().enumerate() {
+        *x = i as u64;
+    }
+
+    let before = rayon::current_thread_index();
+    work.do_work::<J>();
+    let migrated = before != rayon::current_thread_index();
+
+    let start = Instant::now();
+    let sum = working_set.iter().fold(0u64, |acc, &x| acc.wrapping_add(x));
+    let rewarm_ns = start.elapsed().as_nanos() as u64;
+
+    let (ns, count) = if migrated {
+        (&REWARM_MIGRATED_NS, &REWARM_MIGRATED_COUNT)
+    } else {
+        (&REWARM_LOCAL_NS, &REWARM_LOCAL_COUNT)
+    };
+    ns.fetch_add(rewarm_ns, Ordering::Relaxed);
+    count.fetch_add(1, Ordering::Relaxed);
+
+    sum
+}
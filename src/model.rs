@@ -0,0 +1,371 @@
+use crate::analysis::{
+    self, Benchmark, Bootstrap, Estimate, Params, Reference, CLASSIC, CORES_PARAM, LATENCY_HIDING,
+    LATENCY_PARAM, OLD_RAYON, SERIAL,
+};
+
+/// Parameter of benches mapping over an input of this many elements, one leaf each.
+pub const LENGTH_PARAM: &str = "Length";
+/// Parameter of benches whose leaves either compute or wait for this long.
+pub const WORK_PARAM: &str = "Work ms";
+/// Parameter of benches whose leaves wait instead of computing with this probability.
+pub const LATENCY_P_PARAM: &str = "Latency p";
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    /// The parameters don't describe a workload the model knows
+    UnknownWorkload,
+    /// There is neither a serial nor a single core run with these parameters to calibrate with
+    NoReference(Params),
+}
+
+impl std::error::Error for CalibrationError {}
+
+impl std::fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CalibrationError::UnknownWorkload => {
+                write!(f, "The model doesn't know the workload")
+            }
+            CalibrationError::NoReference(params) => write!(
+                f,
+                "No serial or single core {} run with {} to calibrate the model with",
+                CLASSIC, params
+            ),
+        }
+    }
+}
+
+/// What a leaf does besides waiting.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Leaf {
+    /// Computes after possibly waiting, like the map of `map_reduce_fib`
+    LatencyThenCompute,
+    /// Computes only if it doesn't wait, like `Work::LatencyOrCompute`
+    LatencyOrCompute,
+}
+
+/// Leaves of a fork-join DAG, each waiting for `latency_ms` with probability `latency_p` and
+/// computing for `compute_ms` as `leaf` says. Joins are assumed free, so the leaves are all the
+/// work.
+#[derive(Copy, Clone, Debug)]
+pub struct Workload {
+    pub leaves: usize,
+    pub leaf: Leaf,
+    pub compute_ms: f64,
+    pub latency_ms: f64,
+    pub latency_p: f64,
+}
+
+/// How a scheduler spends the time a leaf waits.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Scheduler {
+    /// Runs every leaf in order on a single thread
+    Serial,
+    /// Workers block while their leaf waits
+    Blocking,
+    /// Workers run other leaves while a leaf waits, with perfect overlap
+    LatencyHiding,
+}
+
+impl Scheduler {
+    pub fn of_joiner(joiner: &str) -> Option<Self> {
+        match joiner {
+            SERIAL => Some(Scheduler::Serial),
+            CLASSIC | OLD_RAYON | "New Rayon" => Some(Scheduler::Blocking),
+            LATENCY_HIDING => Some(Scheduler::LatencyHiding),
+            _ => None,
+        }
+    }
+}
+
+impl Workload {
+    /// Expected compute time of a leaf.
+    fn leaf_compute_ms(&self) -> f64 {
+        match self.leaf {
+            Leaf::LatencyThenCompute => self.compute_ms,
+            Leaf::LatencyOrCompute => (1.0 - self.latency_p) * self.compute_ms,
+        }
+    }
+
+    /// Expected time of a leaf that blocks while waiting.
+    fn leaf_ms(&self) -> f64 {
+        self.leaf_compute_ms() + self.latency_p * self.latency_ms
+    }
+
+    /// Longest a single leaf can take if its wait is hidden.
+    fn leaf_span_ms(&self) -> f64 {
+        match (self.leaf, self.latency_p) {
+            (_, p) if p <= 0.0 => self.compute_ms,
+            (Leaf::LatencyThenCompute, _) => self.latency_ms + self.compute_ms,
+            (Leaf::LatencyOrCompute, p) if p >= 1.0 => self.latency_ms,
+            (Leaf::LatencyOrCompute, _) => f64::max(self.latency_ms, self.compute_ms),
+        }
+    }
+
+    /// Expected makespan on `workers` threads, assuming leaves are spread evenly over the workers.
+    pub fn makespan_ms(&self, scheduler: Scheduler, workers: usize) -> f64 {
+        let rounds = self.leaves.div_ceil(workers.max(1)) as f64;
+
+        match scheduler {
+            Scheduler::Serial => self.leaves as f64 * self.leaf_ms(),
+            Scheduler::Blocking => rounds * self.leaf_ms(),
+            // All waits overlap with each other and with compute, so only the longest leaf can
+            // outlast the compute
+            Scheduler::LatencyHiding => {
+                f64::max(rounds * self.leaf_compute_ms(), self.leaf_span_ms())
+            }
+        }
+    }
+
+    /// Workload of the benchmarks with parameters `params`, other than the core count. The compute
+    /// time of a leaf, or for benches without a length the number of leaves, isn't a parameter, so
+    /// it's calibrated from T1 of the run without latency.
+    pub fn calibrate(benchmarks: &[Benchmark], params: &Params) -> Result<Self, CalibrationError> {
+        let t1_ms = |params: Params| match analysis::find_reference(
+            benchmarks,
+            CLASSIC,
+            &params,
+            Reference::Serial,
+        ) {
+            Some(t1) => Ok(analysis::mean(&t1.times_ns) / 1e6),
+            None => Err(CalibrationError::NoReference(params)),
+        };
+        let param = |name: &str| params.get(name)?.parse::<f64>().ok();
+
+        if let (Some(work_ms), Some(latency_p)) = (param(WORK_PARAM), param(LATENCY_P_PARAM)) {
+            let leaves = (t1_ms(params.with(LATENCY_P_PARAM, "0"))? / work_ms).round() as usize;
+
+            return Ok(Workload {
+                leaves,
+                leaf: Leaf::LatencyOrCompute,
+                compute_ms: work_ms,
+                latency_ms: work_ms,
+                latency_p,
+            });
+        }
+
+        let leaves = param(LENGTH_PARAM).ok_or(CalibrationError::UnknownWorkload)? as usize;
+        let latency_ms = param(LATENCY_PARAM).unwrap_or(0.0);
+        let without_latency = match params.get(LATENCY_PARAM) {
+            Some(_) => params.with(LATENCY_PARAM, "0"),
+            None => params.clone(),
+        };
+
+        Ok(Workload {
+            leaves,
+            leaf: Leaf::LatencyThenCompute,
+            compute_ms: t1_ms(without_latency)? / leaves as f64,
+            latency_ms,
+            latency_p: if latency_ms > 0.0 { 1.0 } else { 0.0 },
+        })
+    }
+}
+
+/// Predicted and measured time of one benchmark.
+pub struct PredictionRow {
+    pub joiner: String,
+    /// Parameters other than the core count
+    pub params: Params,
+    pub workers: usize,
+    pub predicted_ms: f64,
+    pub measured_ms: Estimate,
+}
+
+/// Predicted against measured time of every benchmark whose joiner and workload the model knows.
+/// Benches without a core count parameter run on `default_workers` threads, if known. Fails if a
+/// known workload has no run to calibrate with.
+pub fn predictions(
+    benchmarks: &[Benchmark],
+    default_workers: Option<usize>,
+    bootstrap: &mut Bootstrap,
+) -> Result<Vec<PredictionRow>, CalibrationError> {
+    let mut rows = Vec::new();
+
+    for b in benchmarks.iter().filter(|b| !b.times_ns.is_empty()) {
+        let scheduler = match Scheduler::of_joiner(&b.joiner) {
+            Some(scheduler) => scheduler,
+            None => continue,
+        };
+        let params = b.params.without(CORES_PARAM);
+        let workers = match (scheduler, b.params.cores().or(default_workers)) {
+            (Scheduler::Serial, _) => 1,
+            (_, Some(workers)) => workers,
+            (_, None) => continue,
+        };
+        let workload = match Workload::calibrate(benchmarks, &params) {
+            Ok(workload) => workload,
+            Err(CalibrationError::UnknownWorkload) => continue,
+            Err(e) => return Err(e),
+        };
+
+        rows.push(PredictionRow {
+            joiner: b.joiner.clone(),
+            params,
+            workers,
+            predicted_ms: workload.makespan_ms(scheduler, workers),
+            measured_ms: bootstrap.mean(&b.times_ns).map(|t| t / 1e6),
+        });
+    }
+
+    rows.sort_by(|a, b| (&a.params, a.workers, &a.joiner).cmp(&(&b.params, b.workers, &b.joiner)));
+    Ok(rows)
+}
+
+/// Prints rows as a table, with a header for each set of parameters.
+pub struct PredictionTable<'a>(pub &'a [PredictionRow]);
+
+impl std::fmt::Display for PredictionTable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut params = None;
+
+        for row in self.0 {
+            if params != Some(&row.params) {
+                params = Some(&row.params);
+
+                writeln!(f)?;
+                writeln!(f, "{}", row.params)?;
+                writeln!(
+                    f,
+                    "{:<16} {:>7} {:>12} {:<34} measured / predicted",
+                    "joiner", "workers", "predicted ms", "measured ms"
+                )?;
+            }
+
+            writeln!(
+                f,
+                "{:<16} {:>7} {:>12.3} {:<34} {:.3}",
+                row.joiner,
+                row.workers,
+                row.predicted_ms,
+                row.measured_ms.to_string(),
+                row.measured_ms.point / row.predicted_ms
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload(leaf: Leaf, latency_p: f64) -> Workload {
+        Workload {
+            leaves: 100,
+            leaf,
+            compute_ms: 2.0,
+            latency_ms: 10.0,
+            latency_p,
+        }
+    }
+
+    fn benchmark(joiner: &str, params: &str, time_ms: f64) -> Benchmark {
+        Benchmark {
+            joiner: joiner.to_string(),
+            params: Params::parse(params),
+            times_ns: vec![time_ms * 1e6; 10],
+            utilization: None,
+        }
+    }
+
+    #[test]
+    fn makespan_without_latency() {
+        for leaf in [Leaf::LatencyThenCompute, Leaf::LatencyOrCompute] {
+            let w = workload(leaf, 0.0);
+
+            assert_eq!(w.makespan_ms(Scheduler::Serial, 4), 200.0);
+            assert_eq!(w.makespan_ms(Scheduler::Blocking, 4), 50.0);
+            assert_eq!(w.makespan_ms(Scheduler::LatencyHiding, 4), 50.0);
+            // Leaves don't divide evenly over 3 workers
+            assert_eq!(w.makespan_ms(Scheduler::Blocking, 3), 68.0);
+        }
+    }
+
+    #[test]
+    fn makespan_latency_then_compute() {
+        let w = workload(Leaf::LatencyThenCompute, 1.0);
+
+        assert_eq!(w.makespan_ms(Scheduler::Serial, 1), 1200.0);
+        assert_eq!(w.makespan_ms(Scheduler::Blocking, 4), 300.0);
+        assert_eq!(w.makespan_ms(Scheduler::LatencyHiding, 4), 50.0);
+        // With enough workers a single leaf is the critical path
+        assert_eq!(w.makespan_ms(Scheduler::LatencyHiding, 100), 12.0);
+    }
+
+    #[test]
+    fn makespan_latency_or_compute() {
+        let w = workload(Leaf::LatencyOrCompute, 1.0);
+
+        assert_eq!(w.makespan_ms(Scheduler::Serial, 1), 1000.0);
+        assert_eq!(w.makespan_ms(Scheduler::Blocking, 4), 250.0);
+        // Nothing to compute, all waits overlap
+        assert_eq!(w.makespan_ms(Scheduler::LatencyHiding, 4), 10.0);
+
+        let w = workload(Leaf::LatencyOrCompute, 0.5);
+
+        assert_eq!(w.makespan_ms(Scheduler::Serial, 1), 600.0);
+        // A leaf either waits or computes, never both
+        assert_eq!(w.makespan_ms(Scheduler::LatencyHiding, 100), 10.0);
+    }
+
+    #[test]
+    fn calibrate_leaves_from_serial_run() {
+        let benchmarks = [
+            benchmark(SERIAL, "Work ms: 10 | Latency p: 0", 6100.0),
+            benchmark(LATENCY_HIDING, "Work ms: 10 | Latency p: 0.5", 1000.0),
+        ];
+
+        let w = Workload::calibrate(&benchmarks, &Params::parse("Work ms: 10 | Latency p: 0.5"))
+            .unwrap();
+
+        assert_eq!(w.leaves, 610);
+        assert_eq!(w.leaf, Leaf::LatencyOrCompute);
+        assert_eq!(w.compute_ms, 10.0);
+        assert_eq!(w.latency_ms, 10.0);
+        assert_eq!(w.latency_p, 0.5);
+    }
+
+    #[test]
+    fn calibrate_compute_from_single_core_run() {
+        let benchmarks = [
+            benchmark(CLASSIC, "Length: 100 | Latency ms: 0 | Cores: 1", 300.0),
+            benchmark(CLASSIC, "Length: 100 | Latency ms: 50 | Cores: 1", 5300.0),
+        ];
+
+        let w = Workload::calibrate(&benchmarks, &Params::parse("Length: 100 | Latency ms: 50"))
+            .unwrap();
+
+        assert_eq!(w.leaves, 100);
+        assert_eq!(w.leaf, Leaf::LatencyThenCompute);
+        assert_eq!(w.compute_ms, 3.0);
+        assert_eq!(w.latency_ms, 50.0);
+        assert_eq!(w.latency_p, 1.0);
+    }
+
+    #[test]
+    fn calibrate_unknown_workload() {
+        let benchmarks = [benchmark(SERIAL, "Depth: 10", 1.0)];
+
+        assert!(matches!(
+            Workload::calibrate(&benchmarks, &Params::parse("Depth: 10")),
+            Err(CalibrationError::UnknownWorkload)
+        ));
+    }
+
+    #[test]
+    fn calibrate_without_reference() {
+        // Like a core sweep that doesn't start at a single core
+        let benchmarks = [benchmark(
+            CLASSIC,
+            "Length: 100 | Latency ms: 0 | Cores: 4",
+            80.0,
+        )];
+
+        assert!(matches!(
+            Workload::calibrate(&benchmarks, &Params::parse("Length: 100 | Latency ms: 50")),
+            Err(CalibrationError::NoReference(_))
+        ));
+        assert!(predictions(&benchmarks, None, &mut Bootstrap::new(10, 0.95, 0)).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::histograms;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub struct OpenLoopReport {
+    /// Sorted ascending
+    response_times: Vec<Duration>,
+    elapsed: Duration,
+}
+
+impl OpenLoopReport {
+    pub fn completed(&self) -> usize {
+        self.response_times.len()
+    }
+
+    /// Completed requests per second, over the time from the first arrival to the last completion.
+    pub fn throughput(&self) -> f64 {
+        self.completed() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Nearest rank percentile of the response times, `p` in [0.0, 100.0].
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.response_times.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let rank = (p / 100.0 * self.response_times.len() as f64).ceil() as usize;
+        self.response_times[rank.clamp(1, self.response_times.len()) - 1]
+    }
+}
+
+impl std::fmt::Display for OpenLoopReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "completed: {} throughput: {:.2} req/s p50: {:?} p99: {:?} p999: {:?} max: {:?}",
+            self.completed(),
+            self.throughput(),
+            self.percentile(50.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.percentile(100.0)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseRateError {
+    NotPositive,
+    ParseError,
+}
+
+impl std::error::Error for ParseRateError {}
+
+impl std::fmt::Display for ParseRateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseRateError::NotPositive => {
+                write!(f, "Arrival rate must be positive and finite")
+            }
+            ParseRateError::ParseError => {
+                write!(f, "Argument for arrival rate could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_rate(s: &str) -> Result<f64, ParseRateError> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err(ParseRateError::NotPositive),
+        Err(_) => Err(ParseRateError::ParseError),
+    }
+}
+
+/// Runs an open loop benchmark: `requests` requests arrive on a Poisson schedule with `rate`
+/// arrivals per second, independent of whether earlier requests have completed. Each request runs
+/// `job` as a job spawned onto the global Rayon threadpool.
+///
+/// Response times are measured from the scheduled arrival of a request rather than from when it was
+/// actually spawned, so a generator falling behind doesn't hide queueing delays.
+pub fn run_open_loop<F>(rate: f64, requests: usize, seed: u64, job: F) -> OpenLoopReport
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    assert!(
+        rate > 0.0 && rate.is_finite(),
+        "Arrival rate must be positive and finite"
+    );
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let job = Arc::new(job);
+    let (sender, receiver) = mpsc::channel();
+
+    let start = Instant::now();
+    let mut arrival = start;
+    let mut first_arrival = None;
+
+    for _ in 0..requests {
+        // Exponentially distributed inter-arrival times make for a Poisson arrival process
+        let u: f64 = rng.gen();
+        arrival += Duration::from_secs_f64(-(1.0 - u).ln() / rate);
+        first_arrival.get_or_insert(arrival);
+
+        let now = Instant::now();
+        if arrival > now {
+            std::thread::sleep(arrival - now);
+        }
+
+        let job = Arc::clone(&job);
+        let sender = sender.clone();
+
+        rayon::spawn(move || {
+            job();
+
+            let response_time = arrival.elapsed();
+            histograms::record_run(response_time);
+            sender.send(response_time).unwrap();
+        });
+    }
+
+    drop(sender);
+
+    let mut response_times: Vec<Duration> = receiver.iter().collect();
+    // The last response has just come in
+    let elapsed = first_arrival.unwrap_or(start).elapsed();
+    response_times.sort_unstable();
+
+    OpenLoopReport {
+        response_times,
+        elapsed,
+    }
+}
@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::OnceLock;
+
+/// Benches collect counters only if this environment variable is set.
+pub const PERF_COUNTERS_ENV: &str = "PERF_COUNTERS";
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_SOFTWARE: u32 = 1;
+
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_COUNT_SW_CONTEXT_SWITCHES: u64 = 3;
+const PERF_COUNT_SW_CPU_MIGRATIONS: u64 = 4;
+
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+const ATTR_FLAG_DISABLED: u64 = 1 << 0;
+const ATTR_FLAG_INHERIT: u64 = 1 << 1;
+const ATTR_FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+static COUNTERS: OnceLock<PerfCounters> = OnceLock::new();
+
+/// First version of `struct perf_event_attr`, the kernel accepts it for every event used here.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+#[derive(Copy, Clone)]
+pub enum Counter {
+    ContextSwitches,
+    CpuMigrations,
+    Cycles,
+    Instructions,
+    CacheMisses,
+}
+
+impl Counter {
+    const ALL: [Counter; 5] = [
+        Counter::ContextSwitches,
+        Counter::CpuMigrations,
+        Counter::Cycles,
+        Counter::Instructions,
+        Counter::CacheMisses,
+    ];
+
+    fn type_and_config(&self) -> (u32, u64) {
+        match self {
+            Counter::ContextSwitches => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CONTEXT_SWITCHES),
+            Counter::CpuMigrations => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CPU_MIGRATIONS),
+            Counter::Cycles => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+            Counter::Instructions => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+            Counter::CacheMisses => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CACHE_MISSES),
+        }
+    }
+}
+
+impl std::fmt::Display for Counter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Counter::ContextSwitches => write!(f, "context switches"),
+            Counter::CpuMigrations => write!(f, "cpu migrations"),
+            Counter::Cycles => write!(f, "cycles"),
+            Counter::Instructions => write!(f, "instructions"),
+            Counter::CacheMisses => write!(f, "cache misses"),
+        }
+    }
+}
+
+/// Counters of the calling thread and every thread it spawns after opening them, e.g. the workers
+/// of a threadpool built afterwards.
+pub struct PerfCounters {
+    counters: Vec<(Counter, File)>,
+}
+
+pub struct PerfReading {
+    pub values: Vec<(Counter, u64)>,
+}
+
+impl std::fmt::Display for PerfReading {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, (counter, value)) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}: {}", counter, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PerfCounters {
+    /// Opens all counters that are available, counters the hardware or `perf_event_paranoid`
+    /// don't allow are left out. Fails only if no counter could be opened at all.
+    pub fn open() -> io::Result<Self> {
+        let mut counters = Vec::new();
+        let mut last_error = None;
+
+        for counter in Counter::ALL {
+            match open_counter(counter) {
+                Ok(file) => counters.push((counter, file)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) if counters.is_empty() => Err(e),
+            _ => Ok(PerfCounters { counters }),
+        }
+    }
+
+    fn ioctl(&self, request: libc::c_ulong) {
+        for (_, file) in &self.counters {
+            unsafe { libc::ioctl(file.as_raw_fd(), request, 0) };
+        }
+    }
+
+    /// Resets and starts counting.
+    pub fn start(&self) {
+        self.reset();
+        self.resume();
+    }
+
+    /// Stops counting and reads the counters.
+    pub fn stop(&self) -> PerfReading {
+        self.pause();
+        self.read()
+    }
+
+    /// Starts counting again, adding to the current values.
+    pub fn resume(&self) {
+        self.ioctl(PERF_EVENT_IOC_ENABLE);
+    }
+
+    /// Stops counting, keeping the current values.
+    pub fn pause(&self) {
+        self.ioctl(PERF_EVENT_IOC_DISABLE);
+    }
+
+    /// Sets the counters back to zero.
+    pub fn reset(&self) {
+        self.ioctl(PERF_EVENT_IOC_RESET);
+    }
+
+    /// Reads the counters, scaled up if the kernel had to multiplex them.
+    pub fn read(&self) -> PerfReading {
+        let values = self
+            .counters
+            .iter()
+            .filter_map(|(counter, file)| {
+                let mut buf = [0u8; 24];
+                (&*file).read_exact(&mut buf).ok()?;
+
+                let field =
+                    |i: usize| u64::from_ne_bytes(buf[i * 8..i * 8 + 8].try_into().unwrap());
+                let (value, enabled, running) = (field(0), field(1), field(2));
+
+                let scaled = if running > 0 && running < enabled {
+                    (value as f64 * enabled as f64 / running as f64) as u64
+                } else {
+                    value
+                };
+
+                Some((*counter, scaled))
+            })
+            .collect();
+
+        PerfReading { values }
+    }
+}
+
+fn open_counter(counter: Counter) -> io::Result<File> {
+    let (type_, config) = counter.type_and_config();
+
+    let mut attr = PerfEventAttr {
+        type_,
+        size: std::mem::size_of::<PerfEventAttr>() as u32,
+        config,
+        read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+        flags: ATTR_FLAG_DISABLED | ATTR_FLAG_INHERIT,
+        ..Default::default()
+    };
+
+    let open = |attr: &PerfEventAttr| {
+        // pid 0 and cpu -1: the calling thread on any CPU
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                attr as *const PerfEventAttr,
+                0,
+                -1,
+                -1,
+                0,
+            )
+        };
+
+        if fd < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(unsafe { File::from_raw_fd(fd as i32) })
+        }
+    };
+
+    // Counting kernel events needs privileges, only count user space if we don't have them
+    open(&attr).or_else(|_| {
+        attr.flags |= ATTR_FLAG_EXCLUDE_KERNEL | ATTR_FLAG_EXCLUDE_HV;
+        open(&attr)
+    })
+}
+
+/// Opens the counters `measure::Measurement` reports. Needs to be called before building the
+/// threadpool, so the workers inherit the counters.
+pub fn open_counters() -> io::Result<()> {
+    let counters = PerfCounters::open()?;
+    // Opening twice keeps the first counters, which already cover all threads
+    let _ = COUNTERS.set(counters);
+
+    Ok(())
+}
+
+/// `open_counters`, if `PERF_COUNTERS` is set in the environment. Failing to open counters is
+/// reported but otherwise ignored, so benches still run.
+pub fn open_counters_from_env() {
+    if std::env::var_os(PERF_COUNTERS_ENV).is_some() {
+        if let Err(e) = open_counters() {
+            eprintln!("Could not open perf counters: {}", e);
+        }
+    }
+}
+
+/// Runs `f` with the counters enabled, adding to what the next `report` prints. Meant to wrap the
+/// routine inside criterion's `iter`, so criterion's setup and analysis aren't counted.
+pub fn measure<R>(f: impl FnOnce() -> R) -> R {
+    let counters = match counters() {
+        Some(counters) => counters,
+        None => return f(),
+    };
+
+    counters.resume();
+    let r = f();
+    counters.pause();
+
+    r
+}
+
+/// Prints the counters measured since the last report labelled with `label`, if counters were
+/// opened, and resets them.
+pub fn report(label: impl std::fmt::Display) {
+    if let Some(counters) = counters() {
+        println!("{}: {}", label, counters.read());
+        counters.reset();
+    }
+}
+
+pub(crate) fn counters() -> Option<&'static PerfCounters> {
+    COUNTERS.get()
+}
@@ -0,0 +1,382 @@
+use crate::analysis::{self, Benchmark, Estimate, Params, ScalingRow, CORES_PARAM, SERIAL};
+use crate::model::{CalibrationError, Scheduler, Workload};
+use clap::ArgEnum;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+const SIZE: (u32, u32) = (1024, 768);
+const FONT: &str = "sans-serif";
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum Format {
+    Png,
+    Svg,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlotError {
+    Drawing(String),
+}
+
+impl std::error::Error for PlotError {}
+
+impl std::fmt::Display for PlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PlotError::Drawing(e) => write!(f, "Could not draw plot: {}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for PlotError {
+    fn from(e: DrawingAreaErrorKind<E>) -> Self {
+        PlotError::Drawing(e.to_string())
+    }
+}
+
+pub trait Chart {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError>;
+
+    fn save(&self, path: &Path, format: Format) -> Result<(), PlotError> {
+        match format {
+            Format::Png => self.draw(&BitMapBackend::new(path, SIZE).into_drawing_area()),
+            Format::Svg => self.draw(&SVGBackend::new(path, SIZE).into_drawing_area()),
+        }
+    }
+
+    fn to_svg(&self) -> Result<String, PlotError> {
+        let mut svg = String::new();
+        self.draw(&SVGBackend::with_string(&mut svg, SIZE).into_drawing_area())?;
+
+        Ok(svg)
+    }
+}
+
+/// Line with an error bar at every point.
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f64, Estimate)>,
+}
+
+pub struct LineChart {
+    pub title: String,
+    pub x_desc: String,
+    pub y_desc: String,
+    pub series: Vec<Series>,
+}
+
+impl Chart for LineChart {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError> {
+        root.fill(&WHITE)?;
+
+        let points = self.series.iter().flat_map(|series| &series.points);
+        let x_max = points.clone().map(|(x, _)| *x).fold(1.0, f64::max);
+        let y_max = points.map(|(_, y)| y.high).fold(1.0, f64::max);
+
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, (FONT, 24))
+            .margin(16)
+            .x_label_area_size(48)
+            .y_label_area_size(64)
+            .build_cartesian_2d(0.0..x_max * 1.05, 0.0..y_max * 1.1)?;
+
+        chart
+            .configure_mesh()
+            .x_desc(&self.x_desc)
+            .y_desc(&self.y_desc)
+            .draw()?;
+
+        for (i, series) in self.series.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+
+            chart
+                .draw_series(LineSeries::new(
+                    series.points.iter().map(|(x, y)| (*x, y.point)),
+                    color.stroke_width(2),
+                ))?
+                .label(&series.label)
+                .legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                });
+
+            chart.draw_series(series.points.iter().map(|(x, y)| {
+                ErrorBar::new_vertical(*x, y.low, y.point, y.high, color.filled(), 8)
+            }))?;
+        }
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+
+        root.present()?;
+        Ok(())
+    }
+}
+
+/// Values over two categorical parameters, missing values are left blank.
+pub struct Heatmap {
+    pub title: String,
+    pub x_desc: String,
+    pub y_desc: String,
+    pub x_labels: Vec<String>,
+    pub y_labels: Vec<String>,
+    /// Indexed by y, then x
+    pub values: Vec<Vec<Option<f64>>>,
+}
+
+impl Chart for Heatmap {
+    fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>) -> Result<(), PlotError> {
+        root.fill(&WHITE)?;
+
+        let (nx, ny) = (self.x_labels.len(), self.y_labels.len());
+        if nx == 0 || ny == 0 {
+            root.present()?;
+            return Ok(());
+        }
+
+        let values = self.values.iter().flatten().flatten();
+        let min = values.clone().copied().fold(f64::INFINITY, f64::min);
+        let max = values.copied().fold(f64::NEG_INFINITY, f64::max);
+
+        let label = |labels: &[String], value: &SegmentValue<usize>| match value {
+            SegmentValue::CenterOf(i) => labels.get(*i).cloned().unwrap_or_default(),
+            _ => String::new(),
+        };
+        let x_label = |value: &SegmentValue<usize>| label(&self.x_labels, value);
+        let y_label = |value: &SegmentValue<usize>| label(&self.y_labels, value);
+
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, (FONT, 24))
+            .margin(16)
+            .x_label_area_size(48)
+            .y_label_area_size(64)
+            .build_cartesian_2d((0..nx - 1).into_segmented(), (0..ny - 1).into_segmented())?;
+
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc(&self.x_desc)
+            .y_desc(&self.y_desc)
+            .x_labels(nx)
+            .y_labels(ny)
+            .x_label_formatter(&x_label)
+            .y_label_formatter(&y_label)
+            .draw()?;
+
+        let cells = self.values.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter_map(move |(x, value)| value.map(|value| (x, y, value)))
+        });
+
+        let cell = |x: usize, y: usize| {
+            [
+                (SegmentValue::Exact(x), SegmentValue::Exact(y)),
+                (SegmentValue::Exact(x + 1), SegmentValue::Exact(y + 1)),
+            ]
+        };
+        let normalized = |value: f64| {
+            if max > min {
+                (value - min) / (max - min)
+            } else {
+                0.5
+            }
+        };
+
+        chart.draw_series(cells.clone().map(|(x, y, value)| {
+            Rectangle::new(
+                cell(x, y),
+                ViridisRGB
+                    .get_color_normalized(normalized(value), 0.0, 1.0)
+                    .filled(),
+            )
+        }))?;
+
+        chart.draw_series(cells.map(|(x, y, value)| {
+            // Dark end of the color map needs light text
+            let color = if normalized(value) < 0.5 {
+                &WHITE
+            } else {
+                &BLACK
+            };
+            let style = TextStyle::from((FONT, 20).into_font())
+                .color(color)
+                .pos(Pos::new(HPos::Center, VPos::Center));
+
+            Text::new(
+                format!("{:.2}", value),
+                (SegmentValue::CenterOf(x), SegmentValue::CenterOf(y)),
+                style,
+            )
+        }))?;
+
+        root.present()?;
+        Ok(())
+    }
+}
+
+/// Name of a plot file for `title`, with everything but letters and digits replaced by '_'.
+pub fn file_name(title: &str, format: Format) -> String {
+    let mut name = String::new();
+
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+
+    format!("{}.{}", name.trim_matches('_'), format.extension())
+}
+
+/// Speedup over the number of workers of every joiner, one chart for each set of other parameters.
+pub fn speedup_charts(title: &str, rows: &[ScalingRow]) -> Vec<(Params, LineChart)> {
+    let params: BTreeSet<&Params> = rows.iter().map(|row| &row.params).collect();
+
+    params
+        .into_iter()
+        .map(|params| {
+            let rows = rows.iter().filter(|row| row.params == *params);
+            let joiners: BTreeSet<&str> = rows.clone().map(|row| row.joiner.as_str()).collect();
+
+            let series = joiners
+                .into_iter()
+                .map(|joiner| Series {
+                    label: joiner.to_string(),
+                    points: rows
+                        .clone()
+                        .filter(|row| row.joiner == joiner)
+                        .map(|row| (row.cores as f64, row.speedup))
+                        .collect(),
+                })
+                .collect();
+
+            let chart = LineChart {
+                title: format!("{}: {}", title, params),
+                x_desc: "Worker Threads".to_string(),
+                y_desc: "Speedup T1 / TP".to_string(),
+                series,
+            };
+
+            (params.clone(), chart)
+        })
+        .collect()
+}
+
+/// Speedup the model predicts for every scheduler run with `params`, other than the core count, at
+/// the core counts it was run with. Empty if the model doesn't know the workload.
+pub fn model_series(
+    benchmarks: &[Benchmark],
+    params: &Params,
+) -> Result<Vec<Series>, CalibrationError> {
+    let workload = match Workload::calibrate(benchmarks, params) {
+        Ok(workload) => workload,
+        Err(CalibrationError::UnknownWorkload) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let serial_ms = workload.makespan_ms(Scheduler::Serial, 1);
+
+    let runs = benchmarks
+        .iter()
+        .filter(|b| b.params.without(CORES_PARAM) == *params && !b.times_ns.is_empty());
+    let cores: BTreeSet<usize> = runs.clone().filter_map(|b| b.params.cores()).collect();
+
+    let series = [
+        (Scheduler::Blocking, "Model: Blocking"),
+        (Scheduler::LatencyHiding, "Model: Latency Hiding"),
+    ]
+    .into_iter()
+    .filter(|(scheduler, _)| {
+        runs.clone()
+            .any(|b| Scheduler::of_joiner(&b.joiner) == Some(*scheduler))
+    })
+    .map(|(scheduler, label)| Series {
+        label: label.to_string(),
+        points: cores
+            .iter()
+            .map(|&p| {
+                let speedup = serial_ms / workload.makespan_ms(scheduler, p);
+                let exact = Estimate {
+                    point: speedup,
+                    low: speedup,
+                    high: speedup,
+                };
+
+                (p as f64, exact)
+            })
+            .collect(),
+    })
+    .collect();
+
+    Ok(series)
+}
+
+/// Speedup of the mean time of `joiner` over the serial run for every combination of `x_param` and
+/// `y_param`. The serial run is matched on all parameters but `y_param`, since serial runs don't
+/// vary it.
+pub fn speedup_heatmap(
+    title: &str,
+    benchmarks: &[Benchmark],
+    joiner: &str,
+    (x_param, y_param): (&str, &str),
+) -> Heatmap {
+    let runs: Vec<&Benchmark> = benchmarks.iter().filter(|b| b.joiner == joiner).collect();
+
+    let labels = |param: &str| {
+        let mut values: Vec<String> = runs
+            .iter()
+            .filter_map(|b| b.params.get(param))
+            .map(str::to_string)
+            .collect();
+
+        values.sort_by(|a, b| analysis::cmp_values(a, b));
+        values.dedup();
+        values
+    };
+    let (x_labels, y_labels) = (labels(x_param), labels(y_param));
+
+    let mut values = vec![vec![None; x_labels.len()]; y_labels.len()];
+
+    for b in runs {
+        let x = x_labels
+            .iter()
+            .position(|l| Some(l.as_str()) == b.params.get(x_param));
+        let y = y_labels
+            .iter()
+            .position(|l| Some(l.as_str()) == b.params.get(y_param));
+        let serial = benchmarks.iter().find(|s| {
+            s.joiner == SERIAL
+                && !s.times_ns.is_empty()
+                && s.params.without(y_param) == b.params.without(y_param)
+        });
+
+        if let (Some(x), Some(y), Some(serial)) = (x, y, serial) {
+            values[y][x] = Some(analysis::mean(&serial.times_ns) / analysis::mean(&b.times_ns));
+        }
+    }
+
+    Heatmap {
+        title: format!("{}: {} Speedup", title, joiner),
+        x_desc: x_param.to_string(),
+        y_desc: y_param.to_string(),
+        x_labels,
+        y_labels,
+        values,
+    }
+}
@@ -1,8 +1,101 @@
 use crate::{Joiner, Work};
-use rand::distributions::Distribution;
-use rand::distributions::Standard;
+use clap::ArgEnum;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 
 const SERIAL_CUTOFF: usize = 5 * 1024;
+const STRING_KEY_LEN: usize = 16;
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum ElementType {
+    I32,
+    String,
+    Large,
+    Boxed,
+}
+
+impl std::fmt::Display for ElementType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ElementType::I32 => write!(f, "i32"),
+            ElementType::String => write!(f, "String"),
+            ElementType::Large => write!(f, "Large"),
+            ElementType::Boxed => write!(f, "Boxed"),
+        }
+    }
+}
+
+/// Element type that can be randomly generated for sorting benchmarks.
+pub trait RandomElement: Ord + Send + Clone + std::fmt::Debug {
+    fn random<R: Rng>(rng: &mut R) -> Self;
+}
+
+impl RandomElement for i32 {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        rng.gen()
+    }
+}
+
+/// Heap allocated keys, comparisons have to chase a pointer and compare byte strings.
+impl RandomElement for String {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        rng.sample_iter(Alphanumeric)
+            .take(STRING_KEY_LEN)
+            .map(char::from)
+            .collect()
+    }
+}
+
+/// Boxed values, every comparison chases a pointer into memory scattered by the allocator.
+impl RandomElement for Box<i32> {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        Box::new(rng.gen())
+    }
+}
+
+/// 64 byte element (a full cache line on most machines), ordered by its key. The payload is only
+/// there to increase the amount of memory moved around by every swap.
+#[derive(Clone)]
+pub struct LargeElement {
+    key: i64,
+    #[allow(dead_code)] // only moved around by swaps, never read
+    payload: [u64; 7],
+}
+
+impl PartialEq for LargeElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for LargeElement {}
+
+impl PartialOrd for LargeElement {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LargeElement {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl std::fmt::Debug for LargeElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.key)
+    }
+}
+
+impl RandomElement for LargeElement {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        LargeElement {
+            key: rng.gen(),
+            payload: rng.gen(),
+        }
+    }
+}
 
 fn partition<T: Ord>(input: &mut [T]) -> usize {
     let pivot_index = input.len() - 1;
@@ -42,7 +135,89 @@ pub fn quicksort<J: Joiner, T: Ord + Send>(input: &mut [T], work: &Work) {
     }
 }
 
-pub fn generate_random_sequence(len: usize) -> Vec<i32> {
-    let rng = rand::thread_rng();
-    Standard.sample_iter(rng).take(len).collect()
+/// Element whose sort key lives on a remote machine. The key has to be fetched the first time it is
+/// needed for a comparison (possibly incurring latency), after which it is cached on the element.
+#[derive(Clone, Debug)]
+pub struct RemoteKey<T> {
+    key: T,
+    fetched: bool,
+}
+
+impl<T: Ord> RemoteKey<T> {
+    pub fn new(key: T) -> Self {
+        RemoteKey {
+            key,
+            fetched: false,
+        }
+    }
+
+    /// Only valid once the key has been fetched.
+    pub fn key(&self) -> &T {
+        debug_assert!(self.fetched);
+        &self.key
+    }
+
+    fn fetch<J: Joiner>(&mut self, work: &Work) {
+        if !self.fetched {
+            work.maybe_incur_latency::<J>();
+            self.fetched = true;
+        }
+    }
+}
+
+fn partition_remote_keys<J: Joiner, T: Ord>(input: &mut [RemoteKey<T>], work: &Work) -> usize {
+    let pivot_index = input.len() - 1;
+    let mut swap = 0;
+
+    input[pivot_index].fetch::<J>(work);
+
+    for i in 0..pivot_index {
+        input[i].fetch::<J>(work);
+
+        if input[i].key() <= input[pivot_index].key() {
+            if swap != i {
+                input.swap(swap, i);
+            }
+
+            swap += 1;
+        }
+    }
+
+    if swap != pivot_index {
+        input.swap(swap, pivot_index);
+    }
+
+    swap
+}
+
+/// Quicksort where latency is incurred when fetching comparison keys (spread throughout
+/// `partition`), instead of only once per leaf of the computation DAG.
+pub fn quicksort_remote_keys<J: Joiner, T: Ord + Send>(input: &mut [RemoteKey<T>], work: &Work) {
+    if input.len() <= SERIAL_CUTOFF {
+        for element in input.iter_mut() {
+            element.fetch::<J>(work);
+        }
+
+        input.sort_unstable_by(|a, b| a.key().cmp(b.key()));
+    } else {
+        let mid = partition_remote_keys::<J, T>(input, work);
+        let (left, right) = input.split_at_mut(mid);
+
+        J::join(
+            || quicksort_remote_keys::<J, T>(left, work),
+            || quicksort_remote_keys::<J, T>(right, work),
+        );
+    }
+}
+
+pub fn generate_random_elements<T: RandomElement>(len: usize) -> Vec<T> {
+    let mut rng = rand::thread_rng();
+    (0..len).map(|_| T::random(&mut rng)).collect()
+}
+
+pub fn generate_random_remote_keys<T: RandomElement>(len: usize) -> Vec<RemoteKey<T>> {
+    generate_random_elements::<T>(len)
+        .into_iter()
+        .map(RemoteKey::new)
+        .collect()
 }
@@ -0,0 +1,326 @@
+use crate::analysis::{
+    self, Benchmark, Bootstrap, Params, Reference, ScalingRow, CORES_PARAM, SERIAL,
+};
+use crate::metadata::RunMetadata;
+use crate::model::{self, CalibrationError, PredictionRow, LATENCY_P_PARAM, WORK_PARAM};
+use crate::plot::{self, Chart, PlotError};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Parameters `plot::speedup_heatmap` is drawn over, if a group has both.
+const HEATMAP_PARAMS: (&str, &str) = (WORK_PARAM, LATENCY_P_PARAM);
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 1100px; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.6em; text-align: left; }
+th { background: #f0f0f0; }
+td.number { text-align: right; font-family: monospace; }
+svg { max-width: 100%; height: auto; }
+.bar { display: inline-block; width: 8px; height: 24px; margin-right: 1px; background: #ddd;
+       position: relative; vertical-align: bottom; }
+.bar span { position: absolute; bottom: 0; left: 0; width: 100%; background: #2a7; }
+";
+
+/// Escapes text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Static HTML report of a criterion benchmark group: parameters, environment metadata, plots,
+/// speedup tables with confidence intervals and per-worker utilization.
+pub struct HtmlReport<'a> {
+    group: &'a str,
+    metadata: Option<&'a RunMetadata>,
+    benchmarks: &'a [Benchmark],
+    rows: Vec<ScalingRow>,
+    predictions: Result<Vec<PredictionRow>, CalibrationError>,
+    /// Title and SVG of every plot
+    plots: Vec<(String, String)>,
+}
+
+impl<'a> HtmlReport<'a> {
+    pub fn new(
+        group: &'a str,
+        metadata: Option<&'a RunMetadata>,
+        benchmarks: &'a [Benchmark],
+        reference: Reference,
+        bootstrap: &mut Bootstrap,
+    ) -> Result<Self, PlotError> {
+        let rows = analysis::scaling(benchmarks, reference, bootstrap);
+        let workers =
+            metadata.map(|metadata| metadata.allowed_cpus.unwrap_or(metadata.online_cpus));
+        let predictions = model::predictions(benchmarks, workers, bootstrap);
+        let mut plots = Vec::new();
+
+        for (params, mut chart) in plot::speedup_charts(group, &rows) {
+            // A missing reference is reported with the predictions
+            if let Ok(series) = plot::model_series(benchmarks, &params) {
+                chart.series.extend(series);
+            }
+            plots.push((chart.title.clone(), chart.to_svg()?));
+        }
+
+        let (x_param, y_param) = HEATMAP_PARAMS;
+        let joiners: BTreeSet<&str> = benchmarks
+            .iter()
+            .filter(|b| b.joiner != SERIAL)
+            .filter(|b| b.params.get(x_param).is_some() && b.params.get(y_param).is_some())
+            .map(|b| b.joiner.as_str())
+            .collect();
+
+        for joiner in joiners {
+            let heatmap = plot::speedup_heatmap(group, benchmarks, joiner, HEATMAP_PARAMS);
+            plots.push((heatmap.title.clone(), heatmap.to_svg()?));
+        }
+
+        Ok(HtmlReport {
+            group,
+            metadata,
+            benchmarks,
+            rows,
+            predictions,
+            plots,
+        })
+    }
+
+    fn write_metadata(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Environment</h2>")?;
+
+        let fields = self
+            .metadata
+            .and_then(|metadata| serde_json::to_value(metadata).ok());
+        let fields = match fields.as_ref().and_then(|fields| fields.as_object()) {
+            Some(fields) => fields,
+            None => return writeln!(html, "<p>No run metadata was recorded.</p>"),
+        };
+
+        writeln!(html, "<table>")?;
+        for (name, value) in fields {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => "unknown".to_string(),
+                value => value.to_string(),
+            };
+
+            writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(name),
+                escape(&value)
+            )?;
+        }
+        writeln!(html, "</table>")
+    }
+
+    fn write_parameters(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Parameters</h2>")?;
+        writeln!(html, "<table>")?;
+
+        let joiners: BTreeSet<&str> = self.benchmarks.iter().map(|b| b.joiner.as_str()).collect();
+        writeln!(
+            html,
+            "<tr><th>Joiner</th><td>{}</td></tr>",
+            escape(&joiners.into_iter().collect::<Vec<_>>().join(", "))
+        )?;
+
+        let mut names: Vec<&str> = Vec::new();
+        for b in self.benchmarks {
+            for (name, _) in &b.params.0 {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+
+        for name in names {
+            let mut values: Vec<&str> = self
+                .benchmarks
+                .iter()
+                .filter_map(|b| b.params.get(name))
+                .collect();
+            values.sort_by(|a, b| analysis::cmp_values(a, b));
+            values.dedup();
+
+            writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                escape(name),
+                escape(&values.join(", "))
+            )?;
+        }
+
+        writeln!(html, "</table>")
+    }
+
+    fn write_plots(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Plots</h2>")?;
+
+        if self.plots.is_empty() {
+            writeln!(html, "<p>No benchmarks with a core count parameter.</p>")?;
+        }
+
+        for (title, svg) in &self.plots {
+            writeln!(html, "<h3>{}</h3>", escape(title))?;
+            writeln!(html, "{}", svg)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_speedup(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Speedup</h2>")?;
+
+        let params: BTreeSet<&Params> = self.rows.iter().map(|row| &row.params).collect();
+
+        for params in params {
+            writeln!(html, "<h3>{}</h3>", escape(&params.to_string()))?;
+            writeln!(
+                html,
+                "<table><tr><th>Joiner</th><th>Cores</th><th>Samples</th><th>T1</th>\
+                 <th>Time ms</th><th>Speedup</th><th>Efficiency</th><th>Karp–Flatt</th></tr>"
+            )?;
+
+            for row in self.rows.iter().filter(|row| row.params == *params) {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td>\
+                     <td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td>\
+                     <td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                    escape(&row.joiner),
+                    row.cores,
+                    row.samples,
+                    escape(&row.reference),
+                    row.time_ms,
+                    row.speedup,
+                    row.efficiency,
+                    row.karp_flatt
+                        .map_or_else(|| "-".to_string(), |karp_flatt| karp_flatt.to_string())
+                )?;
+            }
+
+            writeln!(html, "</table>")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_predictions(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Model</h2>")?;
+
+        let predictions = match &self.predictions {
+            Ok(predictions) if predictions.is_empty() => {
+                return writeln!(
+                    html,
+                    "<p>The model doesn't know the workload of this group.</p>"
+                );
+            }
+            Ok(predictions) => predictions,
+            Err(e) => return writeln!(html, "<p>{}.</p>", escape(&e.to_string())),
+        };
+
+        let params: BTreeSet<&Params> = predictions.iter().map(|row| &row.params).collect();
+
+        for params in params {
+            writeln!(html, "<h3>{}</h3>", escape(&params.to_string()))?;
+            writeln!(
+                html,
+                "<table><tr><th>Joiner</th><th>Workers</th><th>Predicted ms</th>\
+                 <th>Measured ms</th><th>Measured / Predicted</th></tr>"
+            )?;
+
+            for row in predictions.iter().filter(|row| row.params == *params) {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{:.3}</td>\
+                     <td class=\"number\">{}</td><td class=\"number\">{:.3}</td></tr>",
+                    escape(&row.joiner),
+                    row.workers,
+                    row.predicted_ms,
+                    row.measured_ms,
+                    row.measured_ms.point / row.predicted_ms
+                )?;
+            }
+
+            writeln!(html, "</table>")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_utilization(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<h2>Worker Utilization</h2>")?;
+
+        let mut measured: Vec<&Benchmark> = self
+            .benchmarks
+            .iter()
+            .filter(|b| b.utilization.is_some())
+            .collect();
+
+        if measured.is_empty() {
+            return writeln!(html, "<p>The bench doesn't measure worker utilization.</p>");
+        }
+
+        measured.sort_by(|a, b| {
+            let key = |b: &Benchmark| (b.params.without(CORES_PARAM), b.params.cores());
+            key(a).cmp(&key(b)).then_with(|| a.joiner.cmp(&b.joiner))
+        });
+
+        writeln!(
+            html,
+            "<table><tr><th>Joiner</th><th>Parameters</th><th>Mean</th><th>Workers</th></tr>"
+        )?;
+
+        for b in measured {
+            let utilization = b.utilization.as_ref().unwrap();
+
+            write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td class=\"number\">{:.1}%</td><td>",
+                escape(&b.joiner),
+                escape(&b.params.to_string()),
+                utilization.mean() * 100.0
+            )?;
+
+            for worker in &utilization.workers {
+                let percent = (utilization.fraction(worker) * 100.0).min(100.0);
+                write!(
+                    html,
+                    "<span class=\"bar\" title=\"worker {}: {:.1}%\"><span style=\"height: {:.0}%\"></span></span>",
+                    worker.thread_index, percent, percent
+                )?;
+            }
+
+            writeln!(html, "</td></tr>")?;
+        }
+
+        writeln!(html, "</table>")
+    }
+}
+
+impl std::fmt::Display for HtmlReport<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut html = String::new();
+
+        writeln!(html, "<!DOCTYPE html>")?;
+        writeln!(html, "<html><head><meta charset=\"utf-8\">")?;
+        writeln!(html, "<title>{}</title>", escape(self.group))?;
+        writeln!(html, "<style>{}</style>", STYLE)?;
+        writeln!(html, "</head><body>")?;
+        writeln!(html, "<h1>{}</h1>", escape(self.group))?;
+
+        self.write_parameters(&mut html)?;
+        self.write_metadata(&mut html)?;
+        self.write_plots(&mut html)?;
+        self.write_speedup(&mut html)?;
+        self.write_predictions(&mut html)?;
+        self.write_utilization(&mut html)?;
+
+        writeln!(html, "</body></html>")?;
+
+        f.write_str(&html)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{block_on_future, Joiner};
+use async_io::Timer;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Simulated remote service with a bounded pool of servers and a bounded queue in front of them.
+/// Requests are serviced first come first served, each taking `service_ms` once it reaches a free
+/// server, so the latency of a request grows with the number of requests outstanding. Requests
+/// arriving at a full queue are rejected, and retried after backing off for one service time.
+pub struct ServiceModel {
+    service_time: Duration,
+    queue_capacity: usize,
+    state: Mutex<ServiceState>,
+    requests: AtomicU64,
+    rejections: AtomicU64,
+    queueing_ns: AtomicU64,
+}
+
+struct ServiceState {
+    /// Point in time at which each server finishes its last admitted request
+    free_at: Vec<Instant>,
+    /// Start times of requests admitted to the queue, but not yet being serviced
+    queued: Vec<Instant>,
+}
+
+pub struct ServiceStats {
+    pub requests: u64,
+    pub rejections: u64,
+    pub mean_queueing_delay: Duration,
+}
+
+impl ServiceModel {
+    pub fn new(servers: usize, queue_capacity: usize, service_ms: u64) -> Self {
+        assert!(servers > 0, "Service model needs at least one server");
+
+        ServiceModel {
+            service_time: Duration::from_millis(service_ms),
+            queue_capacity,
+            state: Mutex::new(ServiceState {
+                free_at: vec![Instant::now(); servers],
+                queued: Vec::new(),
+            }),
+            requests: AtomicU64::new(0),
+            rejections: AtomicU64::new(0),
+            queueing_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Issues a request and waits for its response, retrying if it gets rejected.
+    pub fn request<J: Joiner>(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+
+        loop {
+            match self.admit() {
+                Some(response_at) => {
+                    wait_until::<J>(response_at);
+                    return;
+                }
+                None => {
+                    self.rejections.fetch_add(1, Ordering::Relaxed);
+                    wait_until::<J>(Instant::now() + self.service_time);
+                }
+            }
+        }
+    }
+
+    /// Admits a request arriving now, returning when its response will be ready, or `None` if the
+    /// queue is full.
+    fn admit(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        state.queued.retain(|&start| start > now);
+
+        let (server, &free_at) = state
+            .free_at
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &free_at)| free_at)
+            .unwrap();
+        let start = free_at.max(now);
+
+        if start > now {
+            if state.queued.len() >= self.queue_capacity {
+                return None;
+            }
+
+            state.queued.push(start);
+            self.queueing_ns
+                .fetch_add((start - now).as_nanos() as u64, Ordering::Relaxed);
+        }
+
+        state.free_at[server] = start + self.service_time;
+
+        Some(start + self.service_time)
+    }
+
+    pub fn stats(&self) -> ServiceStats {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let queueing_ns = self.queueing_ns.load(Ordering::Relaxed);
+
+        ServiceStats {
+            requests,
+            rejections: self.rejections.load(Ordering::Relaxed),
+            mean_queueing_delay: Duration::from_nanos(queueing_ns / requests.max(1)),
+        }
+    }
+}
+
+impl std::fmt::Display for ServiceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "requests: {} rejections: {} mean queueing delay: {:?}",
+            self.requests, self.rejections, self.mean_queueing_delay
+        )
+    }
+}
+
+fn wait_until<J: Joiner>(deadline: Instant) {
+    if J::is_latency_hiding() {
+        block_on_future::<J, _>(Timer::at(deadline));
+    } else {
+        std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+}
@@ -0,0 +1,127 @@
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const PAINT: u64 = 0x5a5a_5a5a_5a5a_5a5a;
+
+/// Left unpainted right below the frame doing the painting, and right above the guard page
+const MARGIN: usize = 16 * 1024;
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static PAINTED_STACKS: Mutex<Vec<PaintedStack>> = Mutex::new(Vec::new());
+
+struct PaintedStack {
+    thread_index: usize,
+    paint_low: usize,
+    paint_high: usize,
+    stack_low: usize,
+    stack_high: usize,
+}
+
+pub struct StackUsage {
+    pub thread_index: usize,
+    /// High-water mark in bytes
+    pub used: usize,
+    pub size: usize,
+}
+
+impl std::fmt::Display for StackUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "worker {}: stack high-water mark {} KB of {} KB",
+            self.thread_index,
+            self.used / 1024,
+            self.size / 1024
+        )
+    }
+}
+
+/// Paints the stacks of worker threads started from now on, so their high-water marks can be
+/// measured. Painting touches every page of a stack, so it is off by default.
+pub fn enable_tracking() {
+    TRACKING.store(true, Ordering::Relaxed);
+}
+
+/// Start handler for worker threads, paints the unused part of the current thread's stack.
+pub(crate) fn paint_current_stack(thread_index: usize) {
+    if !TRACKING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let (stack_low, stack_high) = match current_stack_bounds() {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    let marker = 0u8;
+    let stack_pointer = &marker as *const u8 as usize;
+
+    let align = std::mem::align_of::<u64>();
+    let paint_low = (stack_low + MARGIN + align - 1) & !(align - 1);
+    let paint_high = (stack_pointer - MARGIN) & !(align - 1);
+
+    for addr in (paint_low..paint_high).step_by(std::mem::size_of::<u64>()) {
+        // SAFETY: the range lies within this thread's stack, well below the current frame, so it
+        // holds no live data
+        unsafe { std::ptr::write_volatile(addr as *mut u64, PAINT) };
+    }
+
+    PAINTED_STACKS.lock().unwrap().push(PaintedStack {
+        thread_index,
+        paint_low,
+        paint_high,
+        stack_low,
+        stack_high,
+    });
+}
+
+/// Exit handler for worker threads, stops tracking the current thread's stack before it is unmapped.
+pub(crate) fn forget_current_stack() {
+    if let Some((stack_low, _)) = current_stack_bounds() {
+        PAINTED_STACKS
+            .lock()
+            .unwrap()
+            .retain(|stack| stack.stack_low != stack_low);
+    }
+}
+
+/// Lowest and highest address of the current thread's stack.
+fn current_stack_bounds() -> Option<(usize, usize)> {
+    unsafe {
+        let mut attr = MaybeUninit::<libc::pthread_attr_t>::uninit();
+        if libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(attr.as_ptr(), &mut addr, &mut size);
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+
+        (result == 0).then(|| (addr as usize, addr as usize + size))
+    }
+}
+
+/// Scans the painted stacks of all live worker threads for the deepest address that was written
+/// to. Only meaningful once workers are idle, e.g. at the end of a run.
+pub fn high_water_marks() -> Vec<StackUsage> {
+    PAINTED_STACKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|stack| {
+            let deepest_write = (stack.paint_low..stack.paint_high)
+                .step_by(std::mem::size_of::<u64>())
+                // SAFETY: the stack belongs to a live pool thread and was painted by it
+                .find(|&addr| unsafe { std::ptr::read_volatile(addr as *const u64) } != PAINT)
+                .unwrap_or(stack.paint_high);
+
+            StackUsage {
+                thread_index: stack.thread_index,
+                used: stack.stack_high - deepest_write,
+                size: stack.stack_high - stack.stack_low,
+            }
+        })
+        .collect()
+}
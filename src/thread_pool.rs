@@ -0,0 +1,199 @@
+use crate::affinity::{self, Placement};
+use crate::{stack, utilization, StackSize};
+use std::any::Any;
+use std::io;
+use std::sync::Arc;
+
+type IndexHandler = Arc<dyn Fn(usize) + Send + Sync>;
+type PanicHandler = Arc<dyn Fn(Box<dyn Any + Send>) + Send + Sync>;
+
+/// Configuration of a Rayon threadpool, which can build global or local pools of both the patched
+/// Rayon and `rayon_old`. Workers are pinned according to the placement, have their stacks painted
+/// if `stack::enable_tracking` was called, and are registered for `utilization::measure`, before
+/// the start handler runs. Every pool built with a placement is pinned, but
+/// `affinity::worker_cpus` only reports the workers of the most recently built pool, and
+/// `utilization::measure` only counts them.
+#[derive(Clone, Default)]
+pub struct ThreadPoolConfig {
+    threads: Option<usize>,
+    stack_size: Option<StackSize>,
+    workload_depth: u32,
+    thread_name: Option<String>,
+    placement: Option<Placement>,
+    start_handler: Option<IndexHandler>,
+    exit_handler: Option<IndexHandler>,
+    panic_handler: Option<PanicHandler>,
+    breadth_first: bool,
+}
+
+#[derive(Debug)]
+pub enum ThreadPoolError {
+    Build(rayon::ThreadPoolBuildError),
+    BuildOld(rayon_old::ThreadPoolBuildError),
+    Placement(io::Error),
+}
+
+impl std::error::Error for ThreadPoolError {}
+
+impl std::fmt::Display for ThreadPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ThreadPoolError::Build(e) => write!(f, "Could not build threadpool: {}", e),
+            ThreadPoolError::BuildOld(e) => {
+                write!(f, "Could not build old Rayon threadpool: {}", e)
+            }
+            ThreadPoolError::Placement(e) => {
+                write!(f, "Could not determine CPU placement of workers: {}", e)
+            }
+        }
+    }
+}
+
+/// Applies a `ThreadPoolConfig` to a `ThreadPoolBuilder`, which has the same interface in both
+/// Rayon versions.
+macro_rules! configure {
+    ($config:expr, $builder:expr) => {{
+        let config: &ThreadPoolConfig = $config;
+        let mut builder = $builder;
+
+        if let Some(threads) = config.threads {
+            builder = builder.num_threads(threads);
+        }
+
+        if let Some(stack_size) = config.stack_size {
+            builder = builder.stack_size(stack_size.mb(config.workload_depth) * 1024 * 1024);
+        }
+
+        if let Some(prefix) = config.thread_name.clone() {
+            builder = builder.thread_name(move |i| format!("{}-{}", prefix, i));
+        }
+
+        if let Some(panic_handler) = config.panic_handler.clone() {
+            builder = builder.panic_handler(move |payload| panic_handler(payload));
+        }
+
+        if config.breadth_first {
+            #[allow(deprecated)]
+            {
+                builder = builder.breadth_first();
+            }
+        }
+
+        let cpu_order = config.cpu_order()?;
+        affinity::forget_pinned();
+        let pool = utilization::next_pool();
+        let start_handler = config.start_handler.clone();
+        let exit_handler = config.exit_handler.clone();
+
+        builder
+            .start_handler(move |i| {
+                if let Some(cpu_order) = &cpu_order {
+                    affinity::pin_current_thread(cpu_order, i);
+                }
+                stack::paint_current_stack(i);
+                utilization::register_current_thread(pool, i);
+
+                if let Some(start_handler) = &start_handler {
+                    start_handler(i);
+                }
+            })
+            .exit_handler(move |i| {
+                if let Some(exit_handler) = &exit_handler {
+                    exit_handler(i);
+                }
+
+                stack::forget_current_stack();
+                utilization::forget_current_thread();
+            })
+    }};
+}
+
+impl ThreadPoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defaults to number of cores on machine
+    pub fn threads(mut self, threads: impl Into<Option<usize>>) -> Self {
+        self.threads = threads.into();
+        self
+    }
+
+    /// Defaults to Rust stack size default, which is 2MB.
+    pub fn stack_size(mut self, stack_size: impl Into<Option<StackSize>>) -> Self {
+        self.stack_size = stack_size.into();
+        self
+    }
+
+    /// Recursion depth of the workload, used to size stacks with `StackSize::Auto`.
+    pub fn workload_depth(mut self, depth: u32) -> Self {
+        self.workload_depth = depth;
+        self
+    }
+
+    /// Workers are named "<prefix>-<index>".
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> Self {
+        self.thread_name = Some(prefix.into());
+        self
+    }
+
+    pub fn placement(mut self, placement: impl Into<Option<Placement>>) -> Self {
+        self.placement = placement.into();
+        self
+    }
+
+    pub fn start_handler(mut self, handler: impl Fn(usize) + Send + Sync + 'static) -> Self {
+        self.start_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn exit_handler(mut self, handler: impl Fn(usize) + Send + Sync + 'static) -> Self {
+        self.exit_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn panic_handler(
+        mut self,
+        handler: impl Fn(Box<dyn Any + Send>) + Send + Sync + 'static,
+    ) -> Self {
+        self.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Workers take jobs from their own deque in FIFO order, like stealing does.
+    pub fn breadth_first(mut self, breadth_first: bool) -> Self {
+        self.breadth_first = breadth_first;
+        self
+    }
+
+    fn cpu_order(&self) -> Result<Option<Vec<usize>>, ThreadPoolError> {
+        self.placement
+            .map(affinity::cpu_order)
+            .transpose()
+            .map_err(ThreadPoolError::Placement)
+    }
+
+    pub fn build(&self) -> Result<rayon::ThreadPool, ThreadPoolError> {
+        configure!(self, rayon::ThreadPoolBuilder::new())
+            .build()
+            .map_err(ThreadPoolError::Build)
+    }
+
+    pub fn build_global(&self) -> Result<(), ThreadPoolError> {
+        configure!(self, rayon::ThreadPoolBuilder::new())
+            .build_global()
+            .map_err(ThreadPoolError::Build)
+    }
+
+    pub fn build_old(&self) -> Result<rayon_old::ThreadPool, ThreadPoolError> {
+        configure!(self, rayon_old::ThreadPoolBuilder::new())
+            .build()
+            .map_err(ThreadPoolError::BuildOld)
+    }
+
+    pub fn build_old_global(&self) -> Result<(), ThreadPoolError> {
+        configure!(self, rayon_old::ThreadPoolBuilder::new())
+            .build_global()
+            .map_err(ThreadPoolError::BuildOld)
+    }
+}
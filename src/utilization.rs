@@ -0,0 +1,187 @@
+use crate::metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Name of the file `save` writes into the criterion directory of a benchmark.
+pub const UTILIZATION_FILE: &str = "utilization.json";
+
+/// Pool and thread index of every live worker thread by thread id.
+static WORKERS: Mutex<BTreeMap<libc::pid_t, (usize, usize)>> = Mutex::new(BTreeMap::new());
+/// Most recently built pool, the only one `measure` counts. Workers of dropped pools can take a
+/// while to exit, and their thread indices collide with the new pool's.
+static CURRENT_POOL: AtomicUsize = AtomicUsize::new(0);
+static MEASURED: Mutex<Measured> = Mutex::new(Measured {
+    wall: Duration::ZERO,
+    busy: BTreeMap::new(),
+});
+
+struct Measured {
+    wall: Duration,
+    /// Thread index and CPU time of every worker seen while measuring, by thread id
+    busy: BTreeMap<libc::pid_t, (usize, Duration)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorkerUtilization {
+    pub thread_index: usize,
+    pub busy_ns: u64,
+}
+
+/// CPU time of every worker over the measured wall-clock time. Idle workers spin for a while
+/// before going to sleep, so this includes time spent looking for work.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Utilization {
+    pub wall_ns: u64,
+    pub workers: Vec<WorkerUtilization>,
+}
+
+impl Utilization {
+    /// Fraction of the wall-clock time `worker` was running.
+    pub fn fraction(&self, worker: &WorkerUtilization) -> f64 {
+        if self.wall_ns == 0 {
+            return 0.0;
+        }
+
+        worker.busy_ns as f64 / self.wall_ns as f64
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.workers.is_empty() {
+            return 0.0;
+        }
+
+        self.workers.iter().map(|w| self.fraction(w)).sum::<f64>() / self.workers.len() as f64
+    }
+
+    pub fn read_json(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl std::fmt::Display for Utilization {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "mean utilization: {:.1}%", self.mean() * 100.0)?;
+
+        for worker in &self.workers {
+            write!(
+                f,
+                " worker {}: {:.1}%",
+                worker.thread_index,
+                self.fraction(worker) * 100.0
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn current_tid() -> libc::pid_t {
+    unsafe { libc::syscall(libc::SYS_gettid) as libc::pid_t }
+}
+
+/// Time the thread has spent running, from the first field of its schedstat.
+fn cpu_time(tid: libc::pid_t) -> Option<Duration> {
+    let schedstat = fs::read_to_string(format!("/proc/self/task/{}/schedstat", tid)).ok()?;
+    let ns = schedstat.split_whitespace().next()?.parse().ok()?;
+
+    Some(Duration::from_nanos(ns))
+}
+
+/// Id for a pool that is about to be built, which replaces the previous pool for `measure`.
+pub(crate) fn next_pool() -> usize {
+    CURRENT_POOL.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Start handler for worker threads of `pool`.
+pub(crate) fn register_current_thread(pool: usize, thread_index: usize) {
+    WORKERS
+        .lock()
+        .unwrap()
+        .insert(current_tid(), (pool, thread_index));
+}
+
+/// Exit handler for worker threads.
+pub(crate) fn forget_current_thread() {
+    WORKERS.lock().unwrap().remove(&current_tid());
+}
+
+fn worker_times() -> BTreeMap<libc::pid_t, (usize, Duration)> {
+    let current_pool = CURRENT_POOL.load(Ordering::Relaxed);
+
+    WORKERS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, &(pool, _))| pool == current_pool)
+        .filter_map(|(&tid, &(_, thread_index))| Some((tid, (thread_index, cpu_time(tid)?))))
+        .collect()
+}
+
+/// Runs `f`, adding its wall-clock time and the CPU time of the workers of the most recently built
+/// pool to the utilization `take` returns. Meant to wrap the routine of a benchmark, so criterion's
+/// own analysis isn't counted.
+pub fn measure<R>(f: impl FnOnce() -> R) -> R {
+    let before = worker_times();
+    let start = Instant::now();
+
+    let r = f();
+
+    let wall = start.elapsed();
+    let after = worker_times();
+
+    let mut measured = MEASURED.lock().unwrap();
+    measured.wall += wall;
+
+    for (tid, (thread_index, end)) in after {
+        // Workers that started while measuring ran from zero
+        let start = before.get(&tid).map_or(Duration::ZERO, |&(_, start)| start);
+        let busy = &mut measured
+            .busy
+            .entry(tid)
+            .or_insert((thread_index, Duration::ZERO))
+            .1;
+        *busy += end.saturating_sub(start);
+    }
+
+    r
+}
+
+/// Utilization measured since the last call, resetting it.
+pub fn take() -> Utilization {
+    let mut measured = MEASURED.lock().unwrap();
+    let wall = std::mem::take(&mut measured.wall);
+
+    let mut workers: Vec<WorkerUtilization> = std::mem::take(&mut measured.busy)
+        .into_values()
+        .map(|(thread_index, busy)| WorkerUtilization {
+            thread_index,
+            busy_ns: busy.as_nanos() as u64,
+        })
+        .collect();
+    workers.sort_by_key(|worker| worker.thread_index);
+
+    Utilization {
+        wall_ns: wall.as_nanos() as u64,
+        workers,
+    }
+}
+
+/// Writes the utilization measured since the last call into the criterion directory of the
+/// benchmark, next to its results. Failing to write it is reported but otherwise ignored, so
+/// benches still run.
+pub fn save(group: &str, function: &str, parameter: &str) {
+    let dir = metadata::criterion_benchmark_dir(group, function, parameter);
+    let path = dir.join(UTILIZATION_FILE);
+    let json = serde_json::to_string_pretty(&take()).expect("Utilization is always serializable");
+
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, json)) {
+        eprintln!("Could not write utilization to {}: {}", path.display(), e);
+    }
+}
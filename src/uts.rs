@@ -0,0 +1,225 @@
+use crate::map_reduce::map_reduce;
+use crate::{Joiner, Work};
+use clap::ArgEnum;
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum TreeType {
+    Binomial,
+    Geometric,
+}
+
+#[derive(Copy, Clone)]
+pub enum TreeShape {
+    /// The root has `root_children` children, every other node has `m` children with probability
+    /// `q` and none otherwise. The expected tree size is finite only if `q * m < 1`, and the closer
+    /// it is to 1 the more unbalanced the tree.
+    Binomial { root_children: u32, q: f64, m: u32 },
+    /// The number of children of a node is geometrically distributed with mean
+    /// `branching_factor`. Nodes at `max_depth` are always leaves.
+    Geometric {
+        branching_factor: f64,
+        max_depth: u32,
+    },
+}
+
+/// Binomial trees have no depth bound, and their depth depends on the seed as much as on `q * m`.
+/// This covers the T3 tree of the UTS suite, around 1500 levels deep and more unbalanced than
+/// binomial trees are usually run with.
+const BINOMIAL_DEPTH_ESTIMATE: u32 = 2000;
+/// Geometric child counts rarely exceed this multiple of their mean plus one.
+const GEOMETRIC_CHILDREN_FACTOR: f64 = 8.0;
+
+/// Levels of joins `map_reduce` nests to split `children` items down to single items.
+fn join_levels(children: u32) -> u32 {
+    u32::BITS - children.saturating_sub(1).leading_zeros()
+}
+
+impl TreeShape {
+    /// Rough estimate of the recursion depth of a traversal, e.g. for sizing stacks. Every tree
+    /// level adds a frame for the node plus the joins over its children.
+    pub fn depth_estimate(&self) -> u32 {
+        match *self {
+            TreeShape::Binomial {
+                root_children, m, ..
+            } => 1 + join_levels(root_children) + BINOMIAL_DEPTH_ESTIMATE * (1 + join_levels(m)),
+            TreeShape::Geometric {
+                branching_factor,
+                max_depth,
+            } => {
+                let children = (GEOMETRIC_CHILDREN_FACTOR * (branching_factor + 1.0)) as u32;
+                max_depth * (1 + join_levels(children))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseBinomialQError {
+    OutOfBounds,
+    ParseError,
+}
+
+impl std::error::Error for ParseBinomialQError {}
+
+impl std::fmt::Display for ParseBinomialQError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseBinomialQError::OutOfBounds => {
+                write!(f, "Probability q must be between 0 and 1")
+            }
+            ParseBinomialQError::ParseError => {
+                write!(f, "Argument for q could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_binomial_q(s: &str) -> Result<f64, ParseBinomialQError> {
+    match s.parse::<f64>() {
+        Ok(q) if (0.0..=1.0).contains(&q) => Ok(q),
+        Ok(_) => Err(ParseBinomialQError::OutOfBounds),
+        Err(_) => Err(ParseBinomialQError::ParseError),
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseBranchingFactorError {
+    NotPositive,
+    ParseError,
+}
+
+impl std::error::Error for ParseBranchingFactorError {}
+
+impl std::fmt::Display for ParseBranchingFactorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseBranchingFactorError::NotPositive => {
+                write!(f, "Branching factor must be positive")
+            }
+            ParseBranchingFactorError::ParseError => {
+                write!(f, "Argument for branching factor could not be parsed")
+            }
+        }
+    }
+}
+
+pub fn parse_branching_factor(s: &str) -> Result<f64, ParseBranchingFactorError> {
+    match s.parse::<f64>() {
+        Ok(branching_factor) if branching_factor > 0.0 && branching_factor.is_finite() => {
+            Ok(branching_factor)
+        }
+        Ok(_) => Err(ParseBranchingFactorError::NotPositive),
+        Err(_) => Err(ParseBranchingFactorError::ParseError),
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct TreeStats {
+    pub nodes: u64,
+    pub leaves: u64,
+    pub max_depth: u32,
+}
+
+impl TreeStats {
+    fn combine(s1: TreeStats, s2: TreeStats) -> TreeStats {
+        TreeStats {
+            nodes: s1.nodes + s2.nodes,
+            leaves: s1.leaves + s2.leaves,
+            max_depth: s1.max_depth.max(s2.max_depth),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Node {
+    state: u64,
+    depth: u32,
+}
+
+/// SplitMix64 finalizer, a cheap hash with good enough avalanche behaviour for generating trees.
+fn hash(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Node {
+    fn root(seed: u64) -> Self {
+        Node {
+            state: hash(seed),
+            depth: 0,
+        }
+    }
+
+    fn child(&self, i: u32) -> Self {
+        Node {
+            state: hash(self.state ^ hash(u64::from(i) + 1)),
+            depth: self.depth + 1,
+        }
+    }
+
+    /// Uniformly distributed in [0, 1), determined by the state of the node.
+    fn uniform(&self) -> f64 {
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn num_children(&self, shape: &TreeShape) -> u32 {
+        match *shape {
+            TreeShape::Binomial { root_children, .. } if self.depth == 0 => root_children,
+            TreeShape::Binomial { q, m, .. } => {
+                if self.uniform() < q {
+                    m
+                } else {
+                    0
+                }
+            }
+            TreeShape::Geometric { max_depth, .. } if self.depth >= max_depth => 0,
+            TreeShape::Geometric {
+                branching_factor, ..
+            } => {
+                // Inverse transform sampling of a geometric distribution with the given mean
+                let p = 1.0 / (1.0 + branching_factor);
+                ((1.0 - self.uniform()).ln() / (1.0 - p).ln()).floor() as u32
+            }
+        }
+    }
+}
+
+/// Unbalanced Tree Search: counts the nodes of an implicitly defined tree, where the number of
+/// children of a node is derived from a hash of the node. The shape of the tree is fixed by the
+/// seed, but can't be predicted without traversing it, so load balancing relies on stealing.
+#[must_use]
+pub fn uts<J: Joiner>(shape: &TreeShape, seed: u64, work: &Work) -> TreeStats {
+    traverse::<J>(Node::root(seed), shape, work)
+}
+
+fn traverse<J: Joiner>(node: Node, shape: &TreeShape, work: &Work) -> TreeStats {
+    // possibly do work, if specified, when expanding a node
+    work.do_work::<J>();
+
+    let num_children = node.num_children(shape);
+
+    if num_children == 0 {
+        return TreeStats {
+            nodes: 1,
+            leaves: 1,
+            max_depth: node.depth,
+        };
+    }
+
+    let mut children: Vec<Node> = (0..num_children).map(|i| node.child(i)).collect();
+    let map = |child: &mut Node| traverse::<J>(*child, shape, work);
+
+    let stats = map_reduce::<J, _, _, _, _, _>(
+        &mut children,
+        &map,
+        &TreeStats::combine,
+        &TreeStats::default,
+    );
+
+    TreeStats {
+        nodes: stats.nodes + 1,
+        ..stats
+    }
+}